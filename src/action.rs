@@ -0,0 +1,217 @@
+use crate::state::Mode;
+#[cfg(test)]
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// Keys are turned into actions before they touch the app, so what a key does can be tested
+// without a terminal. Which key does what is up to the keymap.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Down,
+    Up,
+    Bottom,
+    PageDown,
+    PageUp,
+    // waits for the next key: the same key again jumps to the top, any other grabs the
+    // selected todo
+    Grab,
+    SwitchScreen,
+    NextList,
+    PreviousList,
+    Insert,
+    Edit,
+    // complete on the TODOS screen, reopen on the COMPLETED one
+    Complete,
+    Delete,
+    Fold,
+    Priority,
+    Indent,
+    Outdent,
+    MoveToNextList,
+    MoveToPreviousList,
+    Undo,
+    Redo,
+    // `/`, filters both screens by what is typed
+    Search,
+    // the next or previous todo matching the search
+    NextMatch,
+    PreviousMatch,
+    // shows all todos again
+    ClearSearch,
+    Command,
+    Quit,
+    // enters VISUAL mode, where it leaves again
+    Visual,
+    // GRAB mode, MoveDown and MoveUp move the selection in VISUAL mode too
+    MoveDown,
+    MoveUp,
+    Drop,
+    // VISUAL mode: select the todo under the cursor or not
+    Toggle,
+}
+
+// What the event loop has to do after a key was handled
+#[derive(Debug, PartialEq)]
+pub enum Effect {
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 32] = [
+        Action::Down,
+        Action::Up,
+        Action::Bottom,
+        Action::PageDown,
+        Action::PageUp,
+        Action::Grab,
+        Action::SwitchScreen,
+        Action::NextList,
+        Action::PreviousList,
+        Action::Insert,
+        Action::Edit,
+        Action::Complete,
+        Action::Delete,
+        Action::Fold,
+        Action::Priority,
+        Action::Indent,
+        Action::Outdent,
+        Action::MoveToNextList,
+        Action::MoveToPreviousList,
+        Action::Undo,
+        Action::Redo,
+        Action::Search,
+        Action::NextMatch,
+        Action::PreviousMatch,
+        Action::ClearSearch,
+        Action::Command,
+        Action::Quit,
+        Action::Visual,
+        Action::MoveDown,
+        Action::MoveUp,
+        Action::Drop,
+        Action::Toggle,
+    ];
+
+    // As written in the key config
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Bottom => "bottom",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::Grab => "grab",
+            Action::SwitchScreen => "switch_screen",
+            Action::NextList => "next_list",
+            Action::PreviousList => "previous_list",
+            Action::Insert => "insert",
+            Action::Edit => "edit",
+            Action::Complete => "complete",
+            Action::Delete => "delete",
+            Action::Fold => "fold",
+            Action::Priority => "priority",
+            Action::Indent => "indent",
+            Action::Outdent => "outdent",
+            Action::MoveToNextList => "move_to_next_list",
+            Action::MoveToPreviousList => "move_to_previous_list",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Search => "search",
+            Action::NextMatch => "next_match",
+            Action::PreviousMatch => "previous_match",
+            Action::ClearSearch => "clear_search",
+            Action::Command => "command",
+            Action::Quit => "quit",
+            Action::MoveDown => "move_down",
+            Action::MoveUp => "move_up",
+            Action::Drop => "drop",
+            Action::Visual => "visual",
+            Action::Toggle => "toggle",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    // Whether the action can be bound in `mode`
+    pub fn works_in(self, mode: Mode) -> bool {
+        use Action::*;
+        match mode {
+            Mode::READ => !matches!(self, MoveDown | MoveUp | Drop | Toggle),
+            Mode::GRAB => matches!(self, MoveDown | MoveUp | Drop),
+            Mode::VISUAL => matches!(
+                self,
+                Down | Up
+                    | Bottom
+                    | PageDown
+                    | PageUp
+                    | Complete
+                    | Delete
+                    | MoveToNextList
+                    | MoveToPreviousList
+                    | Visual
+                    | MoveDown
+                    | MoveUp
+                    | Toggle
+            ),
+            _ => false,
+        }
+    }
+}
+
+// Key presses written like `ia<Enter>` for tests: characters stand for themselves, `<Esc>`,
+// `<Enter>`, `<Tab>` and `<C-r>` for the keys with names
+#[cfg(test)]
+pub fn keys(text: &str) -> Vec<KeyEvent> {
+    let mut events = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let named = rest.strip_prefix('<').and_then(|r| r.split_once('>'));
+        let key = match named {
+            Some((name, after)) if name.len() > 1 => {
+                rest = after;
+                match name {
+                    "Esc" => KeyEvent::from(KeyCode::Esc),
+                    "Enter" => KeyEvent::from(KeyCode::Enter),
+                    "Tab" => KeyEvent::from(KeyCode::Tab),
+                    "C-r" => KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL),
+                    _ => panic!("unknown key <{}>", name),
+                }
+            }
+            _ => {
+                rest = &rest[c.len_utf8()..];
+                KeyEvent::from(KeyCode::Char(c))
+            }
+        };
+        events.push(key);
+    }
+    events
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(action.name()), Some(action));
+        }
+        assert_eq!(Action::MoveToNextList.name(), "move_to_next_list");
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(
+            keys("a<Esc><<>"),
+            [
+                KeyEvent::from(KeyCode::Char('a')),
+                KeyEvent::from(KeyCode::Esc),
+                KeyEvent::from(KeyCode::Char('<')),
+                KeyEvent::from(KeyCode::Char('<')),
+                KeyEvent::from(KeyCode::Char('>')),
+            ]
+        );
+    }
+}
@@ -1,450 +1,2576 @@
-use crate::state::{Mode, Screen, State};
+use crate::action::{Action, Effect};
+use crate::codec::{self, Section};
+use crate::editor::LineEditor;
+use crate::export::{self, Format};
+use crate::history::{History, Snapshot};
+use crate::keymap::Keymap;
+use crate::merge;
+use crate::search;
+use crate::state::{Autosave, Confirm, Mode, Screen, State};
+use crate::storage::Storage;
+use crate::theme::Theme;
+use crate::todo::{self, Todo};
+use crate::todotxt;
 use crate::ui;
-use crossterm::event::{self, Event, KeyCode};
-use ratatui::prelude::*;
-use std::{fs, io};
+use crate::wrap;
+use chrono::Utc;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{prelude::*, widgets::ListItem};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
 pub struct App {
     // State of the app
     pub state: State,
+    // Where the todos are loaded from and saved to
+    pub storage: Box<dyn Storage>,
+    pub autosave: Autosave,
+    // the lists as they were last loaded or saved, to tell if there are unsaved changes
+    saved: Vec<Section>,
+    // our lists at that time, the base of a three-way merge
+    base: Vec<Section>,
+    // a change on disk the user chose not to deal with yet
+    ignored: Option<Vec<Section>>,
+    // result of the last command, shown in the status bar until the next key press
+    pub message: Option<String>,
     // Data Store for the app
-    active_todos: Vec<String>,
-    completed_todos: Vec<String>,
-    // for responsive app
+    active_todos: Vec<Todo>,
+    completed_todos: Vec<Todo>,
+    // every todo list of the file, the todos of the one on screen are kept in the fields above
+    lists: Vec<Section>,
+    // index of the list on screen
+    pub tab: usize,
+    // for responsive app, heights of the panels
     pub active_screen_length: u16,
     pub completed_screen_length: u16,
     pub info_length: u16,
+    pub active_screen_width: u16,
+    pub completed_screen_width: u16,
+    // first row shown in each panel
+    pub active_offset: usize,
+    pub completed_offset: usize,
     // currently selected todos
     pub active_selected: usize,
     pub completed_selected: usize,
-    // Constant INFO
-    pub info_texts: Vec<String>,
+    // highest todo id handed out so far
+    next_id: u64,
+    // undo / redo stacks
+    pub history: History,
+    // text being typed in INSERT and EDIT mode
+    pub editor: LineEditor,
+    // first key of a two key command, `g` for `gg`
+    pub pending_key: Option<char>,
+    // question waiting for an answer in CONFIRM mode
+    pub confirm: Option<Confirm>,
+    // the search kept with Enter in SEARCH mode, both screens only show what matches it
+    pub search: Option<String>,
+    // todos selected one by one in VISUAL mode, by id
+    pub marked: Vec<u64>,
+    // id of the todo VISUAL mode started on, everything from it to the cursor is selected
+    pub anchor: Option<u64>,
+    // which key does what in READ and GRAB mode, also shown in the KEY BINDING panel
+    pub keymap: Keymap,
+    pub theme: Theme,
 }
 
 impl App {
-    pub fn new() -> Self {
-        let mut app = App {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        let lists = storage.empty();
+        App {
             state: State {
                 mode: Mode::READ,
                 active: Screen::ACTIVE,
             },
+            storage,
+            autosave: Autosave::Off,
+            saved: Vec::new(),
+            base: Vec::new(),
+            ignored: None,
+            message: None,
             active_todos: Vec::new(),
-            completed_todos:  Vec::new(),
+            completed_todos: Vec::new(),
+            lists,
+            tab: 0,
             active_screen_length: 0,
             completed_screen_length: 0,
-            info_length:0,
-            active_selected:0,
-            completed_selected:0,
-            info_texts: vec![
-                String::from("Click TAB to switch Tabs\nClick i to Insert \nClick e to Edit\nClick g to enter Grab mode\nClick q to Exit"),
-                String::from("Click TAB to switch Tabs\nClick g to enter Grab mode\nClick q to Exit")
-            ]
-        };
-        app.load();
-        app
+            info_length: 0,
+            active_screen_width: 0,
+            completed_screen_width: 0,
+            active_offset: 0,
+            completed_offset: 0,
+            active_selected: 0,
+            completed_selected: 0,
+            next_id: 0,
+            history: History::default(),
+            editor: LineEditor::default(),
+            pending_key: None,
+            confirm: None,
+            search: None,
+            marked: Vec::new(),
+            anchor: None,
+            keymap: Keymap::default(),
+            theme: Theme::default(),
+        }
     }
 }
 
+fn find_list<'a>(lists: &'a [Section], name: &str) -> Option<&'a Section> {
+    lists.iter().find(|list| list.name == name)
+}
+
+// `## Bugs` -> `Bugs`
+fn list_name(name: &str) -> &str {
+    name.trim_start_matches('#').trim()
+}
+
+// How often the file is checked for changes made by someone else
+const WATCH: Duration = Duration::from_secs(1);
+
 impl App {
-    pub fn start<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<bool> {
+    pub fn start<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        let mut last_key = Instant::now();
         loop {
+            if self.autosave == Autosave::Change && self.can_autosave() {
+                self.autosave();
+            }
             terminal.draw(|frame| ui(frame, self))?;
 
-            // Handle events
+            // Handle events, while waiting for them look for changes to the file and save
+            // once the user is idle
+            if !event::poll(WATCH)? {
+                if let Autosave::Idle(delay) = self.autosave {
+                    if last_key.elapsed() >= delay && self.can_autosave() {
+                        self.autosave();
+                    }
+                }
+                self.watch();
+                continue;
+            }
 
-            // set states
             if let Event::Key(key) = event::read()? {
-                // Skip events where keys are not pressed
-                if key.kind == event::KeyEventKind::Release {
-                    continue;
+                last_key = Instant::now();
+                if self.handle_key(key).contains(&Effect::Quit) {
+                    return Ok(());
                 }
+            }
+        }
+    }
+
+    // Everything a key press does to the app, the terminal is left to the event loop
+    pub fn handle_key(&mut self, key: KeyEvent) -> Vec<Effect> {
+        // Skip events where keys are not pressed
+        if key.kind == KeyEventKind::Release {
+            return Vec::new();
+        }
+        self.message = None;
+
+        // `g` waits for the next key: `gg` jumps to the top, any other key grabs the
+        // selected todo and is handled in GRAB mode, so `gj` / `gk` move it right away
+        if self.pending_key.take() == Some('g') {
+            if self.bound(key) == Some(Action::Grab) {
+                *self.selected_mut() = self.shown().first().copied().unwrap_or(0);
+                return Vec::new();
+            }
+            if self.can_grab() {
+                self.checkpoint("move");
+                self.state.mode = Mode::GRAB;
+            }
+        }
+
+        match self.state.mode {
+            Mode::READ | Mode::GRAB | Mode::VISUAL => match self.bound(key) {
+                Some(action) => self.perform(action),
+                None => Vec::new(),
+            },
+            Mode::CONFIRM => self.answer(key),
+            Mode::COMMAND => self.command_key(key),
+            Mode::SEARCH => {
+                self.search_key(key);
+                Vec::new()
+            }
+            Mode::EDIT => {
+                self.edit_key(key);
+                Vec::new()
+            }
+            Mode::INSERT => {
+                self.insert_key(key);
+                Vec::new()
+            }
+        }
+    }
 
-                // State is set in following way:
-                // check what button user clicked
-                // change state (set mode)
-
-                // Event is handled in following pattern
-                // Check which mode user is in
-                // Check which screen user is in
-                // Check what button user pressed
-
-                // handle user input , in read mode (vim motions to go up and down)
-                match self.state.mode {
-                    Mode::READ => match self.state.active {
-                        Screen::ACTIVE => match key.code {
-                            KeyCode::Char('j') => {
-                                let length = self.active_todos.len();
-                                if length == 0 {
-                                    continue;
-                                }
-                                self.active_selected = (self.active_selected + 1) % length;
-                            }
-                            KeyCode::Char('k') => {
-                                let length = self.active_todos.len();
-                                if length == 0 {
-                                    continue;
-                                }
-                                if self.active_selected == 0 {
-                                    self.active_selected = length;
-                                }
-                                self.active_selected = self.active_selected - 1;
-                            }
-                            KeyCode::Char('m') => {
-                                if self.active_todos.len() == 0 {
-                                    continue;
-                                }
-                                let val: String = self.active_todos.remove(self.active_selected);
-                                if self.active_todos.len() != 0 {
-                                    self.active_selected =
-                                        self.active_selected % self.active_todos.len();
-                                }
-                                let mut temp: Vec<String> = Vec::from([val]);
-                                temp.extend(self.completed_todos.clone());
-                                self.completed_todos = temp;
-                                self.completed_selected += 1;
-                            }
-                            _ => {}
-                        },
-                        Screen::COMPLETED => match key.code {
-                            KeyCode::Char('j') => {
-                                let length = self.completed_todos.len();
-                                if length == 0 {
-                                    continue;
-                                }
-                                self.completed_selected = (self.completed_selected + 1) % length;
-                            }
-                            KeyCode::Char('k') => {
-                                let length = self.completed_todos.len();
-                                if length == 0 {
-                                    continue;
-                                }
-                                if self.completed_selected == 0 {
-                                    self.completed_selected = length;
-                                }
-                                self.completed_selected = self.completed_selected - 1;
-                            }
-                            _ => {}
-                        },
-                    },
-                    Mode::EDIT => match self.state.active {
-                        Screen::ACTIVE => match key.code {
-                            KeyCode::Esc | KeyCode::Enter => {
-                                if self.active_todos[self.active_selected].trim().len() == 0 {
-                                    self.active_todos.remove(self.active_selected);
-                                    if self.active_todos.len() == 0 {
-                                        continue;
-                                    } else {
-                                        self.active_selected =
-                                            self.active_selected + 1 % self.active_todos.len();
-                                    }
-                                }
-                                self.state.mode = Mode::READ
-                            }
-                            KeyCode::Char(item) => {
-                                self.active_todos[self.active_selected].push(item);
-                                continue;
-                            }
-                            KeyCode::Backspace => {
-                                self.active_todos[self.active_selected].pop();
-                                continue;
-                            }
-                            _ => {}
-                        },
-                        Screen::COMPLETED => {}
-                    },
-                    Mode::INSERT => {
-                        match key.code {
-                            KeyCode::Esc => {
-                                // Cleaning empty string when leaving insert mode
-                                if self.active_todos[self.active_todos.len() - 1].trim().len() == 0
-                                {
-                                    self.active_todos.pop();
-                                    let _ = self.active_selected.checked_sub(1);
-                                }
-                                self.state.mode = Mode::READ;
-                                continue;
-                            }
-                            KeyCode::Char(a) => {
-                                let index = self.active_todos.len() - 1;
-                                self.active_todos[index].push(a);
-                                self.active_selected = self.active_todos.len() - 1;
-                                continue;
-                            }
-                            KeyCode::Backspace => {
-                                let index = self.active_todos.len() - 1;
-                                self.active_todos[index].pop();
-                                self.active_selected = self.active_todos.len() - 1;
-                                continue;
-                            }
-                            KeyCode::Enter => {
-                                self.active_todos.push(String::new());
-                                self.active_selected += 1;
-                                continue;
-                            }
-                            _ => continue,
-                        }
+    fn bound(&self, key: KeyEvent) -> Option<Action> {
+        self.keymap
+            .action(&self.state.mode, &self.state.active, key)
+    }
+
+    pub fn perform(&mut self, action: Action) -> Vec<Effect> {
+        let visual = self.state.mode == Mode::VISUAL;
+        match action {
+            Action::Visual if visual => self.state.mode = Mode::READ,
+            Action::Complete if visual => self.complete_selection(),
+            Action::Delete if visual => {
+                let count = self.selection().len();
+                if count > 0 {
+                    self.confirm = Some(Confirm::DeleteSelection(count));
+                    self.state.mode = Mode::CONFIRM;
+                }
+            }
+            Action::MoveToNextList if visual => self.move_selection_to_list(true),
+            Action::MoveToPreviousList if visual => self.move_selection_to_list(false),
+            Action::MoveDown | Action::MoveUp if visual => {
+                self.move_selection(action == Action::MoveDown)
+            }
+            Action::Visual => self.begin_visual(),
+            Action::Toggle => self.toggle_selected(),
+            Action::Down => self.step(true),
+            Action::Up => self.step(false),
+            Action::Bottom => *self.selected_mut() = self.shown().last().copied().unwrap_or(0),
+            Action::PageDown => self.page(true),
+            Action::PageUp => self.page(false),
+            Action::Grab => self.pending_key = Some('g'),
+            Action::SwitchScreen => {
+                self.state.active = match self.state.active {
+                    Screen::ACTIVE => Screen::COMPLETED,
+                    Screen::COMPLETED => Screen::ACTIVE,
+                }
+            }
+            Action::NextList => self.switch_list(true),
+            Action::PreviousList => self.switch_list(false),
+            Action::Insert => {
+                if self.state.active == Screen::ACTIVE {
+                    // new todos would not match
+                    self.search = None;
+                    self.checkpoint("insert");
+                    self.state.mode = Mode::INSERT;
+                    // initalize empty todo to insert
+                    let todo = self.new_todo();
+                    self.active_todos.push(todo);
+                    self.active_selected = self.rows() - 1;
+                    self.editor = LineEditor::default();
+                }
+            }
+            Action::Edit => {
+                if !self.list().is_empty() {
+                    self.checkpoint("edit");
+                    self.begin_edit();
+                }
+            }
+            Action::Complete => self.complete_selected(),
+            Action::Delete => {
+                if let Some(todo) = self.selected_todo() {
+                    let text = todo.text.clone();
+                    let subtasks = todo::rows(&todo.children).len();
+                    self.confirm = Some(Confirm::Delete(text, subtasks));
+                    self.state.mode = Mode::CONFIRM;
+                }
+            }
+            // fold / unfold subtasks
+            Action::Fold => {
+                if let Some(todo) = self.selected_todo() {
+                    if !todo.children.is_empty() {
+                        todo.collapsed = !todo.collapsed;
                     }
-                    Mode::GRAB => match self.state.active {
-                        Screen::ACTIVE => match key.code {
-                            KeyCode::Esc | KeyCode::Enter => {
-                                self.state.mode = Mode::READ;
-                                continue;
-                            }
-                            KeyCode::Char('j') => {
-                                let index = (self.active_selected + 1) % self.active_todos.len();
-                                self.active_todos.swap(self.active_selected, index);
-                                self.active_selected = index;
-                            }
-                            KeyCode::Char('k') => {
-                                let index: usize;
-                                if self.active_selected == 0 {
-                                    index = self.active_todos.len() - 1;
-                                } else {
-                                    index = self.active_selected - 1;
-                                }
-                                self.active_todos.swap(self.active_selected, index);
-                                self.active_selected = index;
-                            }
-                            _ => {}
-                        },
-                        Screen::COMPLETED => match key.code {
-                            KeyCode::Esc | KeyCode::Enter => {
-                                self.state.mode = Mode::READ;
-                                continue;
-                            }
-                            KeyCode::Char('j') => {
-                                let index =
-                                    (self.completed_selected + 1) % self.completed_todos.len();
-                                self.completed_todos.swap(self.completed_selected, index);
-                                self.completed_selected = index;
-                            }
-                            KeyCode::Char('k') => {
-                                let index: usize;
-                                if self.completed_selected == 0 {
-                                    index = self.completed_todos.len() - 1;
-                                } else {
-                                    index = self.completed_selected - 1;
-                                }
-                                self.completed_todos.swap(self.completed_selected, index);
-                                self.completed_selected = index;
-                            }
-                            _ => {}
-                        },
-                    },
                 }
+            }
+            // cycle through no priority, (A), (B) and (C)
+            Action::Priority => {
+                if self.selected_path().is_none() {
+                    return Vec::new();
+                }
+                self.checkpoint("priority");
+                let todo = self.selected_todo().unwrap();
+                todo.priority = match todo.priority {
+                    None => Some('A'),
+                    Some('A') => Some('B'),
+                    Some('B') => Some('C'),
+                    Some(_) => None,
+                };
+                self.settle();
+            }
+            Action::Indent => self.reparent(todo::indent),
+            Action::Outdent => self.reparent(todo::outdent),
+            Action::MoveToNextList => self.move_to_list(true),
+            Action::MoveToPreviousList => self.move_to_list(false),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Search => {
+                self.state.mode = Mode::SEARCH;
+                self.editor = LineEditor::default();
+            }
+            Action::NextMatch => self.jump(true),
+            Action::PreviousMatch => self.jump(false),
+            Action::ClearSearch => self.search = None,
+            Action::Command => {
+                self.state.mode = Mode::COMMAND;
+                self.editor = LineEditor::default();
+            }
+            Action::Quit => {
+                if self.save_and_quit() {
+                    return vec![Effect::Quit];
+                }
+            }
+            // todos are swapped with their siblings, subtasks move along
+            Action::MoveDown | Action::MoveUp => {
+                if let Some(path) = self.selected_path() {
+                    let down = action == Action::MoveDown;
+                    let moved = todo::swap_sibling(self.list_mut(), &path, down);
+                    *self.selected_mut() = todo::row_of(self.list(), &moved).unwrap();
+                }
+            }
+            Action::Drop => {
+                self.settle();
+                self.state.mode = Mode::READ;
+            }
+        }
+        Vec::new()
+    }
 
-                // change screen
-                if key.code == KeyCode::Tab {
-                    if self.state.active == Screen::ACTIVE {
-                        self.state.active = Screen::COMPLETED;
-                        continue;
-                    } else {
-                        self.state.active = Screen::ACTIVE;
-                        continue;
-                    }
+    // Answer the question asked in CONFIRM mode
+    fn answer(&mut self, key: KeyEvent) -> Vec<Effect> {
+        match (&self.confirm, key.code) {
+            (Some(Confirm::CompleteChildren(_)), KeyCode::Char('y')) => self.finish_complete(true),
+            (Some(Confirm::CompleteChildren(_)), KeyCode::Char('n')) => self.finish_complete(false),
+            (Some(Confirm::Delete(..)), KeyCode::Char('y')) => self.delete_selected(),
+            (Some(Confirm::DeleteSelection(_)), KeyCode::Char('y')) => self.delete_selection(),
+            (Some(Confirm::SaveFailed(_)), KeyCode::Char('y')) => return vec![Effect::Quit],
+            (Some(Confirm::Conflict), KeyCode::Char('r')) => {
+                if let Err(err) = self.reload() {
+                    self.message = Some(format!("Reload failed: {}", err));
                 }
+            }
+            (Some(Confirm::Conflict), KeyCode::Char('o')) => {
+                self.message = Some(match self.save() {
+                    Ok(()) => format!("Saved {}", self.storage.name()),
+                    Err(err) => format!("Saving failed: {}", err),
+                });
+            }
+            (Some(Confirm::Conflict), KeyCode::Char('m')) => {
+                self.message = Some(match self.merge() {
+                    Ok(()) => String::from("Merged the changes on disk"),
+                    Err(err) => format!("Merge failed: {}", err),
+                });
+            }
+            (Some(Confirm::Conflict), KeyCode::Esc) => {
+                self.ignored = self.changed_on_disk();
+            }
+            (Some(Confirm::Conflict), _) => return Vec::new(),
+            (_, KeyCode::Char('n')) | (_, KeyCode::Esc) => {}
+            _ => return Vec::new(),
+        }
+        self.confirm = None;
+        self.state.mode = Mode::READ;
+        Vec::new()
+    }
 
-                // Enter INSERT mode only if user presses i in active screen
-                if key.code == KeyCode::Char('i') && self.state.active == Screen::ACTIVE {
-                    self.state.mode = Mode::INSERT;
-                    // initalize empty string to insert
-                    self.active_todos.push(String::new());
-                    self.active_selected = self.active_todos.len() - 1;
-                    continue;
+    fn command_key(&mut self, key: KeyEvent) -> Vec<Effect> {
+        match key.code {
+            KeyCode::Esc => self.state.mode = Mode::READ,
+            KeyCode::Enter => {
+                self.state.mode = Mode::READ;
+                let command = self.editor.text().trim().to_string();
+                if self.run_command(&command) {
+                    return vec![Effect::Quit];
                 }
+            }
+            _ => {
+                self.editor.handle(key);
+            }
+        }
+        Vec::new()
+    }
 
-                // below modes are allowed for both screens
-                if key.code == KeyCode::Char('g') {
-                    match self.state.active {
-                        Screen::ACTIVE => {
-                            if self.active_todos.len() < 2 {
-                                continue;
-                            } else {
-                                self.state.mode = Mode::GRAB;
-                                continue;
-                            }
-                        }
-                        Screen::COMPLETED => {
-                            if self.completed_todos.len() < 2 {
-                                continue;
-                            } else {
-                                self.state.mode = Mode::GRAB;
-                                continue;
-                            }
-                        }
-                    }
+    // The todos are filtered while the search is typed, Enter keeps the filter and Esc drops
+    // it
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.search = None;
+                self.state.mode = Mode::READ;
+            }
+            KeyCode::Enter => {
+                self.search = self.query().map(String::from);
+                self.state.mode = Mode::READ;
+            }
+            _ => {
+                if self.editor.handle(key) {
+                    self.follow_search();
                 }
+            }
+        }
+    }
 
-                if key.code == KeyCode::Char('e') {
-                    match self.state.active {
-                        Screen::ACTIVE => {
-                            if self.active_todos.len() == 0 {
-                                continue;
-                            } else {
-                                self.state.mode = Mode::EDIT;
-                                continue;
-                            }
-                        }
-                        Screen::COMPLETED => {
-                            if self.completed_todos.len() == 0 {
-                                continue;
-                            } else {
-                                self.state.mode = Mode::EDIT;
-                                continue;
-                            }
-                        }
-                    }
+    // both screens edit the selected todo the same way
+    fn edit_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter => self.finish_edit(),
+            _ => {
+                if self.editor.handle(key) {
+                    let text = self.editor.text().to_string();
+                    self.selected_todo().unwrap().text = text;
                 }
+            }
+        }
+    }
 
-                // exit if q
-                if key.code == KeyCode::Char('q') {
-                    self.save();
-                    return Ok(true);
+    fn insert_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                // Cleaning empty string when leaving insert mode
+                if self.active_todos.last().unwrap().text.trim().is_empty() {
+                    self.active_todos.pop();
+                    self.clamp_selected();
+                }
+                self.settle();
+                self.state.mode = Mode::READ;
+            }
+            KeyCode::Enter => {
+                let todo = self.new_todo();
+                self.active_todos.push(todo);
+                self.active_selected = self.rows() - 1;
+                self.editor = LineEditor::default();
+            }
+            _ => {
+                if self.editor.handle(key) {
+                    let text = self.editor.text().to_string();
+                    self.active_todos.last_mut().unwrap().text = text;
                 }
             }
         }
     }
-    pub fn update_len(&mut self, c: u16, a: u16, i: u16) {
-        self.completed_screen_length = c;
-        self.active_screen_length = a;
-        self.info_length = i;
+
+    // Save before quitting, returns false if saving failed: then the user has to agree to
+    // lose the changes first
+    fn save_and_quit(&mut self) -> bool {
+        // the file is left alone when there is nothing to save
+        if !self.is_dirty() {
+            return true;
+        }
+        if self.ask_about_conflict() {
+            return false;
+        }
+        match self.save() {
+            Ok(()) => true,
+            Err(err) => {
+                self.confirm = Some(Confirm::SaveFailed(err.to_string()));
+                self.state.mode = Mode::CONFIRM;
+                false
+            }
+        }
+    }
+
+    // Run a command typed after `:`, returns true if the app should exit
+    fn run_command(&mut self, command: &str) -> bool {
+        match command.split_once(' ') {
+            // in the format the extension asks for
+            Some(("export", path)) => {
+                let path = Path::new(path.trim());
+                let text = self.export(Format::of(path));
+                self.message = Some(match self.storage.export(path, &text) {
+                    Ok(()) => format!("Exported {} todos to {}", self.count(), path.display()),
+                    Err(err) => format!("Export failed: {}", err),
+                });
+                return false;
+            }
+            Some(("import", path)) => {
+                let path = Path::new(path.trim());
+                let lists = self
+                    .storage
+                    .import(path)
+                    .and_then(|text| export::import(&text, Format::of(path)));
+                self.message = Some(match lists {
+                    Ok(lists) => {
+                        self.import(lists, false);
+                        format!("Imported {} todos from {}", self.count(), path.display())
+                    }
+                    Err(err) => format!("Import failed: {}", err),
+                });
+                return false;
+            }
+            _ => {}
+        }
+        match command {
+            "" => false,
+            "w" | "wq" | "x" if self.ask_about_conflict() => false,
+            "w" => {
+                self.message = Some(match self.save() {
+                    Ok(()) => format!("Saved {}", self.storage.name()),
+                    Err(err) => format!("Saving failed: {}", err),
+                });
+                false
+            }
+            "q" if self.is_dirty() => {
+                self.message = Some(String::from(
+                    "Unsaved changes, use :wq to save or :q! to quit without saving",
+                ));
+                false
+            }
+            "q" | "q!" => true,
+            "wq" | "x" => self.save_and_quit(),
+            _ => {
+                self.message = Some(format!("Unknown command :{}", command));
+                false
+            }
+        }
     }
 
-    pub fn show_active_todos(&mut self) -> Text {
-        let mut text = Text::from(vec![]);
-        let mut index = 0;
+    // Whether the todos differ from what was last loaded or saved
+    pub fn is_dirty(&self) -> bool {
+        codec::stored(&self.sections()) != self.saved
+    }
 
-        for span in &self.active_todos {
-            if index == self.active_selected {
-                let line = Line::from(("[ ] ".to_owned() + span + "\n").white());
-                text.push_line(line);
-            } else {
-                let line = Line::from("[ ] ".to_owned() + span + "\n").cyan();
-                text.push_line(line);
+    // Changes are only saved once they are finished, not while a todo is being typed
+    fn can_autosave(&self) -> bool {
+        self.state.mode == Mode::READ && self.is_dirty()
+    }
+
+    fn autosave(&mut self) {
+        // never overwrite changes made by someone else, watch() asks what to do about them
+        if self.changed_on_disk().is_some() {
+            return;
+        }
+        if let Err(err) = self.save() {
+            self.message = Some(format!("Autosave failed: {}", err));
+        }
+    }
+
+    // The todos on disk if someone else changed them since we last loaded or saved
+    fn changed_on_disk(&self) -> Option<Vec<Section>> {
+        match self.storage.watch() {
+            Ok(theirs) => theirs,
+            // todos we can't read are still not ours to overwrite
+            Err(_) => Some(Vec::new()),
+        }
+    }
+
+    // Ask what to do about a change on disk, returns false if there is none
+    fn ask_about_conflict(&mut self) -> bool {
+        if self.changed_on_disk().is_none() {
+            return false;
+        }
+        self.confirm = Some(Confirm::Conflict);
+        self.state.mode = Mode::CONFIRM;
+        true
+    }
+
+    // Called while waiting for keys, asks once about every change on disk
+    fn watch(&mut self) {
+        if self.state.mode != Mode::READ {
+            return;
+        }
+        if let Some(now) = self.changed_on_disk() {
+            if self.ignored.as_ref() != Some(&now) {
+                self.ask_about_conflict();
             }
-            index += 1;
         }
-        text
     }
 
-    pub fn show_completed_todos(&self) -> Text {
-        let mut text = Text::from(vec![]);
-        let mut index = 0;
+    // Throw away our changes for the file on disk, undo brings them back
+    fn reload(&mut self) -> io::Result<()> {
+        let lists = self.storage.load()?;
+        self.checkpoint("reload");
+        self.take_over(lists);
+        self.settle();
+        Ok(())
+    }
 
-        for span in &self.completed_todos {
-            if index == self.completed_selected {
-                let line = Line::from(("[x] ".to_owned() + span + "\n").white());
-                text.push_line(line);
-            } else {
-                let line = Line::from("[x] ".to_owned() + span + "\n").cyan();
-                text.push_line(line);
+    // Three-way merge of our todos and the ones on disk against the version we loaded, the
+    // result is not saved until the user does
+    fn merge(&mut self) -> io::Result<()> {
+        let mut theirs = self.storage.load()?;
+        let mut seen = HashSet::new();
+        for list in &mut theirs {
+            if let Some(base) = find_list(&self.base, &list.name) {
+                merge::adopt_ids(&base.todos, &mut list.todos);
+            }
+            let next_id = &mut self.next_id;
+            todo::for_each_mut(&mut list.todos, &mut |todo| {
+                if todo.id == 0 || !seen.insert(todo.id) {
+                    *next_id += 1;
+                    todo.id = *next_id;
+                }
+            });
+        }
+
+        self.checkpoint("merge");
+        let mut merged = Vec::new();
+        for ours in self.sections() {
+            let Some(other) = find_list(&theirs, &ours.name) else {
+                merged.push(ours);
+                continue;
+            };
+            let base = find_list(&self.base, &ours.name)
+                .cloned()
+                .unwrap_or_default();
+            let (passthrough, below) =
+                if (&ours.passthrough, &ours.below) == (&base.passthrough, &base.below) {
+                    (other.passthrough.clone(), other.below.clone())
+                } else {
+                    (ours.passthrough, ours.below)
+                };
+            merged.push(Section {
+                todos: merge::merge(&base.todos, &ours.todos, &other.todos),
+                name: ours.name,
+                passthrough,
+                below,
+            });
+        }
+        // lists someone else added
+        for other in &theirs {
+            if find_list(&merged, &other.name).is_none() {
+                merged.push(other.clone());
             }
-            index += 1;
         }
-        text
+        self.show_list(merged, self.tab);
+        self.saved = codec::stored(&theirs);
+        self.base = theirs;
+        self.settle();
+        Ok(())
     }
 
-    pub fn deserialize(&self) -> String {
-        let mut response = String::new();
-        // serialize completed todos to follow md syntax
-        self.completed_todos
+    // Todos in the order they are written to the file: completed todos go back to where they
+    // were among the active ones, those that never were come first
+    pub fn file_order(&self) -> Vec<Todo> {
+        let len = self.active_todos.len();
+        let mut todos: Vec<(usize, &Todo)> = self
+            .completed_todos
             .iter()
             .rev()
-            .for_each(|elem| response += format!("- [x] {}\n", elem).as_str());
-        // serialize incompleted todos to follow md syntax
-        self.active_todos
+            .map(|todo| (todo.origin.map_or(0, |index| index.min(len) + 1), todo))
+            .chain(
+                self.active_todos
+                    .iter()
+                    .enumerate()
+                    .map(|(i, todo)| (i + 1, todo)),
+            )
+            .collect();
+        // stable, a completed todo comes before the active one that took its place
+        todos.sort_by_key(|(at, _)| *at);
+        todos.into_iter().map(|(_, todo)| todo.clone()).collect()
+    }
+
+    // The list on screen as it is written to the file
+    pub fn current(&self) -> Section {
+        let list = &self.lists[self.tab];
+        Section {
+            name: list.name.clone(),
+            passthrough: list.passthrough.clone(),
+            below: list.below.clone(),
+            todos: self.file_order(),
+        }
+    }
+
+    // Every list as it is written to the file
+    pub fn sections(&self) -> Vec<Section> {
+        let mut lists = self.lists.clone();
+        lists[self.tab] = self.current();
+        lists
+    }
+
+    // Take over `lists` with list `tab` on screen
+    pub fn show_list(&mut self, mut lists: Vec<Section>, tab: usize) {
+        if lists.is_empty() {
+            lists.push(Section::default());
+        }
+        self.tab = tab.min(lists.len() - 1);
+        let todos = std::mem::take(&mut lists[self.tab].todos);
+        self.lists = lists;
+        self.set_todos(todos);
+    }
+
+    // Names of the lists for the tabs, without the heading marker
+    pub fn list_names(&self) -> Vec<String> {
+        self.lists
             .iter()
-            .for_each(|elem| response += format!("- [ ] {}\n", elem).as_str());
-        response
+            .map(|list| list_name(&list.name).to_string())
+            .collect()
     }
 
-    pub fn serialize(&mut self, text: String) {
-        let data: Vec<&str> = text.split('\n').collect();
-        for elem in data {
-            if elem.trim().len() == 0 {
-                continue;
+    // Show the next (`forward`) or previous list, starting at the top
+    pub fn switch_list(&mut self, forward: bool) {
+        let tab = self.other_list(forward);
+        let lists = self.sections();
+        self.show_list(lists, tab);
+        self.active_selected = 0;
+        self.completed_selected = 0;
+        self.active_offset = 0;
+        self.completed_offset = 0;
+    }
+
+    fn other_list(&self, forward: bool) -> usize {
+        let len = self.lists.len();
+        if forward {
+            (self.tab + 1) % len
+        } else {
+            (self.tab + len - 1) % len
+        }
+    }
+
+    // Move the selected todo with its subtasks to the end of the next (`forward`) or previous
+    // list, where it stays on the same screen
+    pub fn move_to_list(&mut self, forward: bool) {
+        let tab = self.other_list(forward);
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        if tab == self.tab {
+            return;
+        }
+        self.checkpoint("move to list");
+        let todo = todo::remove(self.list_mut(), &path);
+        self.add_to_list(tab, todo);
+        self.clamp_selected();
+        self.message = Some(format!("Moved to {}", list_name(&self.lists[tab].name)));
+    }
+
+    // Append a todo to the list of another tab
+    fn add_to_list(&mut self, tab: usize, mut todo: Todo) {
+        todo.origin = None;
+        self.lists[tab].todos.push(todo);
+    }
+
+    pub fn export(&self, format: Format) -> String {
+        export::export(&self.sections(), format)
+    }
+
+    // Take over imported todos in file order. They replace the todos of the list they name
+    // keeping their ids, unless `append`: then they go after them with new ids. Todos of lists
+    // this file does not have go to the list on screen.
+    pub fn import(&mut self, imported: Vec<Section>, append: bool) {
+        self.checkpoint("import");
+        let mut lists = self.sections();
+        let mut replaced = HashSet::new();
+        for list in imported {
+            let tab = lists
+                .iter()
+                .position(|other| other.name == list.name)
+                .unwrap_or(self.tab);
+            let mut todos = list.todos;
+            if append {
+                todo::for_each_mut(&mut todos, &mut |todo| todo.id = 0);
             }
-            let m_elem: Vec<&str> = elem.split("- [").collect();
-            let content = m_elem[1].to_string();
-            if content.starts_with("x") {
-                self.set_completed_todos(content[3..].to_string());
-            } else {
-                self.set_active_todos(content[3..].to_string())
+            // two imported lists can end up in the same one
+            if append || !replaced.insert(tab) {
+                let mut all = std::mem::take(&mut lists[tab].todos);
+                all.append(&mut todos);
+                todos = all;
             }
+            lists[tab].todos = todos;
         }
+        self.show_list(lists, self.tab);
+        self.assign_ids();
+        self.settle();
     }
 
-    // reading file directly to variables as i know my readme files are not going to be huge
-    pub fn save(&self) {
-        let file: String;
-        match fs::read_to_string("readme.md") {
-            Ok(content) => {
-                file = content;
-            }
-            Err(_) => {
-                let _ = fs::File::create("readme.md");
-                file = "".to_string()
+    // Number of todos in every list, subtasks included
+    fn count(&self) -> usize {
+        let mut count = 0;
+        for list in self.sections() {
+            todo::for_each(&list.todos, &mut |_| count += 1);
+        }
+        count
+    }
+
+    // Replace all todos by ones in file order, the top level todo decides which list a tree
+    // belongs to
+    pub fn set_todos(&mut self, todos: Vec<Todo>) {
+        self.active_todos.clear();
+        self.completed_todos.clear();
+        for mut todo in todos {
+            if todo.done {
+                todo.origin = Some(self.active_todos.len());
+                self.set_completed_todos(todo);
+            } else {
+                self.set_active_todos(todo);
             }
+        }
+        self.clamp_selected();
+    }
+
+    // Column of the cursor in the status bar while typing a command or a search
+    pub fn command_cursor(&self) -> Option<usize> {
+        if self.state.mode != Mode::COMMAND && self.state.mode != Mode::SEARCH {
+            return None;
+        }
+        Some(1 + self.editor.text()[..self.editor.cursor()].width())
+    }
+
+    // Empty todo with a fresh id, created now
+    pub fn new_todo(&mut self) -> Todo {
+        self.next_id += 1;
+        Todo {
+            id: self.next_id,
+            created: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    // Give every todo loaded without an id one that is not used in the file yet, the same goes
+    // for a todo whose id an earlier one has already, e.g. after copying a line by hand
+    fn assign_ids(&mut self) {
+        let mut lists = vec![&mut self.active_todos, &mut self.completed_todos];
+        lists.extend(self.lists.iter_mut().map(|list| &mut list.todos));
+        let mut max = self.next_id;
+        for list in &lists {
+            todo::for_each(list, &mut |todo| max = max.max(todo.id));
+        }
+        let mut seen = HashSet::new();
+        for list in lists {
+            todo::for_each_mut(list, &mut |todo| {
+                if todo.id == 0 || !seen.insert(todo.id) {
+                    max += 1;
+                    todo.id = max;
+                }
+            });
+        }
+        self.next_id = max;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            active: self.active_todos.clone(),
+            completed: self.completed_todos.clone(),
+            active_selected: self.active_selected,
+            completed_selected: self.completed_selected,
+            lists: self.lists.clone(),
+            tab: self.tab,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.lists = snapshot.lists;
+        self.tab = snapshot.tab;
+        self.active_todos = snapshot.active;
+        self.completed_todos = snapshot.completed;
+        self.active_selected = snapshot.active_selected;
+        self.completed_selected = snapshot.completed_selected;
+        self.clamp_selected();
+    }
+
+    // Remember the current todos before changing them so the change can be undone
+    fn checkpoint(&mut self, label: &'static str) {
+        let snapshot = self.snapshot();
+        self.history.record(label, snapshot);
+    }
+
+    // Drop the last checkpoint if nothing changed since
+    fn settle(&mut self) {
+        let snapshot = self.snapshot();
+        self.history.settle(&snapshot);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.history.undo(self.snapshot()) {
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.history.redo(self.snapshot()) {
+            self.restore(snapshot);
+        }
+    }
+
+    // Todos of the current screen
+    fn list(&self) -> &Vec<Todo> {
+        self.list_on(self.state.active)
+    }
+
+    fn list_mut(&mut self) -> &mut Vec<Todo> {
+        match self.state.active {
+            Screen::ACTIVE => &mut self.active_todos,
+            Screen::COMPLETED => &mut self.completed_todos,
+        }
+    }
+
+    fn selected_mut(&mut self) -> &mut usize {
+        self.selected_on_mut(self.state.active)
+    }
+
+    fn selected_on_mut(&mut self, screen: Screen) -> &mut usize {
+        match screen {
+            Screen::ACTIVE => &mut self.active_selected,
+            Screen::COMPLETED => &mut self.completed_selected,
+        }
+    }
+
+    fn list_on(&self, screen: Screen) -> &Vec<Todo> {
+        match screen {
+            Screen::ACTIVE => &self.active_todos,
+            Screen::COMPLETED => &self.completed_todos,
+        }
+    }
+
+    fn selected_on(&self, screen: Screen) -> usize {
+        match screen {
+            Screen::ACTIVE => self.active_selected,
+            Screen::COMPLETED => self.completed_selected,
+        }
+    }
+
+    // The search filtering the todos, while it is typed too
+    pub fn query(&self) -> Option<&str> {
+        let query = if self.state.mode == Mode::SEARCH {
+            Some(self.editor.text())
+        } else {
+            self.search.as_deref()
         };
-        let mut content = "## Todo\n".to_string() + &self.deserialize();
-        match file.find("## Todo") {
-            Some(index) => {
-                let res: Vec<&str> = file[..index].split("## Todo\n\n").collect();
-                content = res[0].to_string() + &content;
-                let _ = fs::write("readme.md", content);
-            }
-            None => {
-                content = file + &content;
-                let _ = fs::write("readme.md", content);
+        query.filter(|query| !query.is_empty())
+    }
+
+    // Rows of `screen` matching the search
+    fn matches_on(&self, screen: Screen) -> Vec<usize> {
+        let Some(query) = self.query() else {
+            return Vec::new();
+        };
+        todo::rows(self.list_on(screen))
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| search::find(query, &display_text(row.todo)).is_some())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // Rows shown on `screen`: while searching only the matching ones, the todos they are
+    // subtasks of and the selected one. Selections stay rows of the whole list.
+    fn shown_on(&self, screen: Screen) -> Vec<usize> {
+        let list = self.list_on(screen);
+        let rows = todo::rows(list).len();
+        if self.query().is_none() {
+            return (0..rows).collect();
+        }
+        let mut shown = vec![false; rows];
+        if let Some(selected) = shown.get_mut(self.selected_on(screen)) {
+            *selected = true;
+        }
+        for row in self.matches_on(screen) {
+            let path = todo::path_of(list, row).unwrap();
+            for depth in 1..=path.len() {
+                shown[todo::row_of(list, &path[..depth]).unwrap()] = true;
             }
+        }
+        (0..rows).filter(|row| shown[*row]).collect()
+    }
+
+    fn shown(&self) -> Vec<usize> {
+        self.shown_on(self.state.active)
+    }
+
+    // Position of the selection among the rows shown on `screen`
+    pub fn shown_selected(&self, screen: Screen) -> usize {
+        let selected = self.selected_on(screen);
+        let shown = self.shown_on(screen);
+        shown.iter().position(|row| *row == selected).unwrap_or(0)
+    }
+
+    // Select the next or previous shown row, going round at the ends
+    fn step(&mut self, down: bool) {
+        let shown = self.shown();
+        let selected = self.selected_on(self.state.active);
+        let Some(position) = shown.iter().position(|row| *row == selected) else {
+            return;
+        };
+        let next = if down {
+            (position + 1) % shown.len()
+        } else {
+            (position + shown.len() - 1) % shown.len()
         };
+        *self.selected_mut() = shown[next];
     }
 
-    pub fn load(&mut self) {
-        let file = fs::read_to_string("readme.md").unwrap_or("".to_string());
+    // Select the next or previous todo matching the search, going round at the ends
+    fn jump(&mut self, forward: bool) {
+        let Some(query) = self.query() else {
+            self.message = Some(String::from("No search, use / to search"));
+            return;
+        };
+        let matches = self.matches_on(self.state.active);
+        let selected = self.selected_on(self.state.active);
+        let next = if forward {
+            matches
+                .iter()
+                .find(|row| **row > selected)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rfind(|row| **row < selected)
+                .or(matches.last())
+        };
+        match next {
+            Some(next) => *self.selected_mut() = *next,
+            None => self.message = Some(format!("No matches for {}", query)),
+        }
+    }
 
-        match file.find("## Todo") {
-            Some(index) => {
-                let res: Vec<&str> = file[index..].split("## Todo\n").collect();
-                self.serialize(res[1].to_string());
+    // Keep the selections on todos matching the search as it is typed
+    fn follow_search(&mut self) {
+        for screen in [Screen::ACTIVE, Screen::COMPLETED] {
+            let matches = self.matches_on(screen);
+            let selected = self.selected_on(screen);
+            if !matches.contains(&selected) {
+                let next = matches
+                    .iter()
+                    .find(|row| **row > selected)
+                    .or(matches.first());
+                if let Some(next) = next {
+                    *self.selected_on_mut(screen) = *next;
+                }
             }
-            None => {}
-        };
+        }
     }
 
-    pub fn set_active_todos(&mut self, item: String) {
-        self.active_todos.push(item);
+    // Number of visible rows on the current screen
+    fn rows(&self) -> usize {
+        todo::rows(self.list()).len()
     }
 
-    pub fn set_completed_todos(&mut self, item: String) {
-        self.completed_todos.push(item);
+    fn selected_path(&self) -> Option<Vec<usize>> {
+        todo::path_of(self.list(), self.selected_on(self.state.active))
     }
-}
 
-mod test {
-    #[allow(unused)]
-    use super::App;
+    fn selected_todo(&mut self) -> Option<&mut Todo> {
+        let path = self.selected_path()?;
+        todo::get_mut(self.list_mut(), &path)
+    }
 
-    #[test]
-    fn deserializer() {
-        let mut state = App::new();
-        state.active_todos = Vec::from([String::from("class")]);
-        state.completed_todos = Vec::from([String::from("for")]);
+    // grabbing needs a sibling to swap with
+    fn can_grab(&mut self) -> bool {
+        match self.selected_path() {
+            Some(path) => todo::siblings_mut(self.list_mut(), &path).len() > 1,
+            None => false,
+        }
+    }
 
-        let result = String::from("- [x] for\n- [ ] class\n");
-        assert_eq!(state.deserialize(), result);
+    fn screen_width(&self) -> u16 {
+        match self.state.active {
+            Screen::ACTIVE => self.active_screen_width,
+            Screen::COMPLETED => self.completed_screen_width,
+        }
     }
 
-    #[test]
-    fn serializer() {
-        let mut state = App::new();
-        let result = String::from("- [x] for\n- [ ] class\n");
-        state.serialize(result);
+    // Number of wrapped lines of every shown row on the current screen
+    fn heights(&self) -> Vec<usize> {
+        let width = self.screen_width();
+        let rows = todo::rows(self.list());
+        self.shown()
+            .into_iter()
+            .map(|row| &rows[row])
+            .map(|row| {
+                let width = text_width(width, prefix(row).width());
+                wrap::wrap(&display_text(row.todo), width).len()
+            })
+            .collect()
+    }
+
+    // Move the selection a panel height down or up, rows spanning several lines count for
+    // every line
+    pub fn page(&mut self, down: bool) {
+        let height = match self.state.active {
+            Screen::ACTIVE => self.active_screen_length,
+            Screen::COMPLETED => self.completed_screen_length,
+        };
+        // the panel borders take two rows
+        let page = (height as usize).saturating_sub(2).max(1);
+        let heights = self.heights();
+        let shown = self.shown();
+        let mut position = self.shown_selected(self.state.active);
+        let mut lines = 0;
+        while lines < page {
+            let next = if down {
+                position + 1
+            } else {
+                position.wrapping_sub(1)
+            };
+            let Some(height) = heights.get(next) else {
+                break;
+            };
+            lines += height;
+            position = next;
+        }
+        if let Some(row) = shown.get(position) {
+            *self.selected_mut() = *row;
+        }
+    }
 
-        let mut reference = App::new();
-        reference.active_todos = Vec::from([String::from("class")]);
-        reference.completed_todos = Vec::from([String::from("for")]);
+    // keep the selections inside the lists after rows disappeared
+    fn clamp_selected(&mut self) {
+        let active = todo::rows(&self.active_todos).len();
+        let completed = todo::rows(&self.completed_todos).len();
+        self.active_selected = self.active_selected.min(active.saturating_sub(1));
+        self.completed_selected = self.completed_selected.min(completed.saturating_sub(1));
+    }
 
-        assert_eq!(state.active_todos, reference.active_todos);
-        assert_eq!(state.completed_todos, reference.completed_todos);
+    // Indent or outdent the selected todo, keeping it selected
+    fn reparent(&mut self, action: fn(&mut Vec<Todo>, &[usize]) -> Option<Vec<usize>>) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        self.checkpoint("indent");
+        if let Some(moved) = action(self.list_mut(), &path) {
+            *self.selected_mut() = todo::row_of(self.list(), &moved).unwrap();
+        }
+        self.settle();
+    }
+
+    // Mark the selected todo as done, asking first whether unfinished subtasks should be
+    // completed along with it. A todo that is already done is marked as not done again, its
+    // subtasks are left as they are.
+    pub fn complete_selected(&mut self) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        // top level todos on the completed screen go back to the active list
+        if path.len() == 1 && self.state.active == Screen::COMPLETED {
+            self.reopen_selected();
+            return;
+        }
+        let todo = todo::get_mut(self.list_mut(), &path).unwrap();
+        let pending = todo.pending_children();
+        if !todo.done && pending > 0 {
+            self.confirm = Some(Confirm::CompleteChildren(pending));
+            self.state.mode = Mode::CONFIRM;
+            return;
+        }
+        self.finish_complete(false);
+    }
+
+    pub fn finish_complete(&mut self, children: bool) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        self.checkpoint("complete");
+        let todo = todo::get_mut(self.list_mut(), &path).unwrap();
+        if children {
+            todo.set_done_recursive(true);
+        } else {
+            todo.set_done(!todo.done);
+        }
+
+        // finished top level todos move to the front of the completed list
+        if path.len() == 1 && self.state.active == Screen::ACTIVE {
+            self.move_to_completed(path[0]);
+            self.clamp_selected();
+        }
+    }
+
+    // Move a finished top level todo to the front of the completed list, keeping the
+    // completed selection on the todo it was on
+    fn move_to_completed(&mut self, index: usize) {
+        let mut todo = self.active_todos.remove(index);
+        todo.origin = Some(index);
+        if !self.completed_todos.is_empty() {
+            self.completed_selected += todo::rows(std::slice::from_ref(&todo)).len();
+        }
+        self.completed_todos.insert(0, todo);
+    }
+
+    // Enter EDIT mode with the cursor at the end of the selected todo
+    pub fn begin_edit(&mut self) {
+        if let Some(path) = self.selected_path() {
+            let todo = todo::get_mut(self.list_mut(), &path).unwrap();
+            self.editor = LineEditor::new(&todo.text);
+            self.state.mode = Mode::EDIT;
+        }
+    }
+
+    // Row and column of the text cursor on the current screen while typing
+    // counted from the first line shown in the panel
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        if self.state.mode != Mode::EDIT && self.state.mode != Mode::INSERT {
+            return None;
+        }
+        let (selected, offset) = match self.state.active {
+            Screen::ACTIVE => (self.active_selected, self.active_offset),
+            Screen::COMPLETED => (self.completed_selected, self.completed_offset),
+        };
+        let rows = todo::rows(self.list());
+        let row = rows.get(selected)?;
+        let indent = prefix(row).width();
+        let (line, column) = wrap::locate(
+            &display_text(row.todo),
+            text_width(self.screen_width(), indent),
+            self.editor.cursor(),
+        );
+        let position = self.shown_selected(self.state.active);
+        let above: usize = self.heights()[offset.min(position)..position].iter().sum();
+        Some((above + line, indent + column))
+    }
+
+    // Leave EDIT mode, an emptied todo is removed and its subtasks take its place
+    pub fn finish_edit(&mut self) {
+        if let Some(path) = self.selected_path() {
+            let list = self.list_mut();
+            if todo::get_mut(list, &path).unwrap().text.trim().is_empty() {
+                let todo = todo::remove(list, &path);
+                let index = *path.last().unwrap();
+                todo::siblings_mut(list, &path).splice(index..index, todo.children);
+                self.clamp_selected();
+            }
+        }
+        self.settle();
+        self.state.mode = Mode::READ;
+    }
+
+    // Move the selected completed todo back to the active list, where it was before if known
+    pub fn reopen_selected(&mut self) {
+        let Some(path) = todo::path_of(&self.completed_todos, self.completed_selected) else {
+            return;
+        };
+        if path.len() != 1 {
+            return;
+        }
+        self.checkpoint("reopen");
+        self.reopen(path[0]);
+        self.clamp_selected();
+    }
+
+    // Move the top level completed todo at `index` back to the active list
+    fn reopen(&mut self, index: usize) {
+        let mut todo = self.completed_todos.remove(index);
+        todo.set_done(false);
+        let index = todo
+            .origin
+            .take()
+            .unwrap_or(self.active_todos.len())
+            .min(self.active_todos.len());
+
+        // keep the active selection on the todo it was on
+        let rows = todo::rows(std::slice::from_ref(&todo)).len();
+        let before = todo::rows(&self.active_todos[..index]).len();
+        if !self.active_todos.is_empty() && before <= self.active_selected {
+            self.active_selected += rows;
+        }
+        self.active_todos.insert(index, todo);
+    }
+
+    // Remove the selected todo along with its subtasks
+    pub fn delete_selected(&mut self) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        self.checkpoint("delete");
+        todo::remove(self.list_mut(), &path);
+        self.clamp_selected();
+    }
+
+    // Start selecting several todos from the selected one on. Todos are remembered by id as
+    // their rows change when they move, so every todo gets one first.
+    fn begin_visual(&mut self) {
+        self.assign_ids();
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        self.anchor = todo::get_mut(self.list_mut(), &path).map(|todo| todo.id);
+        self.marked.clear();
+        self.state.mode = Mode::VISUAL;
+    }
+
+    // Rows selected in VISUAL mode on the current screen: the shown ones from the anchor to
+    // the cursor and the ones selected one by one
+    pub fn selection(&self) -> Vec<usize> {
+        let list = self.list();
+        let selected = self.selected_on(self.state.active);
+        let anchor = self
+            .anchor
+            .and_then(|id| todo::find_id(list, id))
+            .and_then(|path| todo::row_of(list, &path));
+        let in_range = |index: &usize| {
+            anchor
+                .is_some_and(|anchor| (anchor.min(selected)..=anchor.max(selected)).contains(index))
+        };
+        let shown = self.shown();
+        todo::rows(list)
+            .iter()
+            .enumerate()
+            .filter(|(index, row)| {
+                self.marked.contains(&row.todo.id) || in_range(index) && shown.contains(index)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn selection_ids(&self) -> Vec<u64> {
+        let rows = todo::rows(self.list());
+        self.selection()
+            .into_iter()
+            .map(|row| rows[row].todo.id)
+            .collect()
+    }
+
+    // Paths of the selected todos, without subtasks of selected todos as they go along
+    fn selection_paths(&self) -> Vec<Vec<usize>> {
+        let rows = todo::rows(self.list());
+        let paths: Vec<Vec<usize>> = self
+            .selection()
+            .into_iter()
+            .map(|row| rows[row].path.clone())
+            .collect();
+        paths
+            .iter()
+            .filter(|path| {
+                !paths
+                    .iter()
+                    .any(|other| other.len() < path.len() && path.starts_with(other))
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Select or unselect the todo under the cursor, the range selected so far stays selected
+    fn toggle_selected(&mut self) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        self.marked = self.selection_ids();
+        self.anchor = None;
+        let id = todo::get_mut(self.list_mut(), &path).unwrap().id;
+        match self.marked.iter().position(|marked| *marked == id) {
+            Some(index) => {
+                self.marked.remove(index);
+            }
+            None => self.marked.push(id),
+        }
+    }
+
+    // Complete the selected todos with their subtasks, or reopen them on the COMPLETED
+    // screen
+    fn complete_selection(&mut self) {
+        let paths = self.selection_paths();
+        if paths.is_empty() {
+            return;
+        }
+        let active = self.state.active == Screen::ACTIVE;
+        self.checkpoint(if active { "complete" } else { "reopen" });
+        if active {
+            // from the bottom up so the paths above stay valid
+            for path in paths.iter().rev() {
+                todo::get_mut(&mut self.active_todos, path)
+                    .unwrap()
+                    .set_done_recursive(true);
+                if path.len() == 1 {
+                    self.move_to_completed(path[0]);
+                }
+            }
+        } else {
+            // from the top down so todos go back in front of the ones that were below them
+            let mut reopened = 0;
+            for path in &paths {
+                let mut path = path.clone();
+                path[0] -= reopened;
+                if path.len() == 1 {
+                    self.reopen(path[0]);
+                    reopened += 1;
+                } else {
+                    todo::get_mut(&mut self.completed_todos, &path)
+                        .unwrap()
+                        .set_done(false);
+                }
+            }
+        }
+        self.clamp_selected();
+        self.state.mode = Mode::READ;
+        let done = if active { "Completed" } else { "Reopened" };
+        self.message = Some(format!("{} {} todo(s)", done, paths.len()));
+    }
+
+    pub fn delete_selection(&mut self) {
+        let paths = self.selection_paths();
+        self.checkpoint("delete");
+        for path in paths.iter().rev() {
+            todo::remove(self.list_mut(), path);
+        }
+        self.clamp_selected();
+        self.message = Some(format!("Deleted {} todo(s)", paths.len()));
+    }
+
+    // Move the selected todos to the list of the next or previous tab, in their order
+    fn move_selection_to_list(&mut self, forward: bool) {
+        let tab = self.other_list(forward);
+        let paths = self.selection_paths();
+        if tab == self.tab || paths.is_empty() {
+            return;
+        }
+        self.checkpoint("move to list");
+        let mut todos: Vec<Todo> = paths
+            .iter()
+            .rev()
+            .map(|path| todo::remove(self.list_mut(), path))
+            .collect();
+        todos.reverse();
+        for todo in todos {
+            self.add_to_list(tab, todo);
+        }
+        self.clamp_selected();
+        self.state.mode = Mode::READ;
+        self.message = Some(format!(
+            "Moved {} todo(s) to {}",
+            paths.len(),
+            list_name(&self.lists[tab].name)
+        ));
+    }
+
+    // Move every selected todo past its next (`down`) or previous sibling. Todos at the end
+    // of their list stay, and so do the ones next to them that are selected, so a block of
+    // todos moves together. The selection and the cursor stay on their todos.
+    fn move_selection(&mut self, down: bool) {
+        let mut ids = self.selection_ids();
+        if ids.is_empty() {
+            return;
+        }
+        let cursor = self
+            .selected_path()
+            .and_then(|path| todo::get_mut(self.list_mut(), &path))
+            .map(|todo| todo.id);
+        self.marked = ids.clone();
+        self.anchor = None;
+        self.checkpoint("move");
+        // the todo moving first makes room for the ones behind it
+        if down {
+            ids.reverse();
+        }
+        for id in &ids {
+            let list = self.list_mut();
+            let path = todo::find_id(list, *id).unwrap();
+            let index = *path.last().unwrap();
+            let siblings = todo::siblings_mut(list, &path);
+            let other = match down {
+                true if index + 1 < siblings.len() => index + 1,
+                false if index > 0 => index - 1,
+                _ => continue,
+            };
+            if !ids.contains(&siblings[other].id) {
+                todo::swap_sibling(list, &path, down);
+            }
+        }
+        self.settle();
+        let list = self.list();
+        if let Some(row) = cursor
+            .and_then(|id| todo::find_id(list, id))
+            .and_then(|path| todo::row_of(list, &path))
+        {
+            *self.selected_mut() = row;
+        }
+    }
+
+    pub fn update_width(&mut self, c: u16, a: u16) {
+        self.completed_screen_width = c;
+        self.active_screen_width = a;
+    }
+
+    pub fn update_len(&mut self, c: u16, a: u16, i: u16) {
+        self.completed_screen_length = c;
+        self.active_screen_length = a;
+        self.info_length = i;
+    }
+
+    // The selection of VISUAL mode if `screen` is where it is made
+    fn selection_on(&self, screen: Screen) -> Vec<usize> {
+        if self.state.mode == Mode::VISUAL && self.state.active == screen {
+            self.selection()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn show_active_todos(&self) -> Vec<ListItem<'static>> {
+        show_todos(
+            &self.active_todos,
+            &self.shown_on(Screen::ACTIVE),
+            self.active_selected,
+            &self.selection_on(Screen::ACTIVE),
+            self.query(),
+            self.active_screen_width,
+            &self.theme,
+        )
+    }
+
+    pub fn show_completed_todos(&self) -> Vec<ListItem<'static>> {
+        show_todos(
+            &self.completed_todos,
+            &self.shown_on(Screen::COMPLETED),
+            self.completed_selected,
+            &self.selection_on(Screen::COMPLETED),
+            self.query(),
+            self.completed_screen_width,
+            &self.theme,
+        )
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        let lists = self.sections();
+        self.storage.save(&lists)?;
+        self.saved = codec::stored(&lists);
+        self.base = lists;
+        Ok(())
+    }
+
+    pub fn load(&mut self) -> io::Result<()> {
+        let lists = self.storage.load()?;
+        self.take_over(lists);
+        Ok(())
+    }
+
+    // Start over from the lists last loaded, staying on the list on screen if it is still there
+    fn take_over(&mut self, lists: Vec<Section>) {
+        let name = &self.lists[self.tab].name;
+        let tab = lists.iter().position(|list| list.name == *name);
+        self.show_list(lists, tab.unwrap_or(0));
+        self.assign_ids();
+        self.base = self.sections();
+        self.saved = codec::stored(&self.base);
+    }
+
+    pub fn set_active_todos(&mut self, item: Todo) {
+        self.active_todos.push(item);
+    }
+
+    // completed todos are kept newest first, the file lists them oldest first
+    pub fn set_completed_todos(&mut self, item: Todo) {
+        self.completed_todos.insert(0, item);
+    }
+}
+
+// Everything in front of the text of a row: the indentation by depth, whether the subtasks
+// are folded, the checkbox and the priority
+fn prefix(row: &todo::Row) -> String {
+    let fold = match (row.todo.children.is_empty(), row.todo.collapsed) {
+        (true, _) => "  ",
+        (false, true) => "▸ ",
+        (false, false) => "▾ ",
+    };
+    let mark = if row.todo.done { "[x] " } else { "[ ] " };
+    let mut content = "  ".repeat(row.depth()) + fold + mark;
+    if let Some(priority) = row.todo.priority {
+        content += &format!("({}) ", priority);
+    }
+    content
+}
+
+// The text of a todo as shown, followed by the tags it does not hold already
+fn display_text(todo: &Todo) -> String {
+    let mut content = todo.text.clone();
+    for tag in &todo.tags {
+        if !todotxt::in_text(&todo.text, tag) {
+            content += &format!(" #{}", tag);
+        }
+    }
+    content
+}
+
+// Columns left for the text of a row in a panel `width` wide, 0 before the first draw
+// means there is no limit
+fn text_width(width: u16, indent: usize) -> usize {
+    if width == 0 {
+        return usize::MAX;
+    }
+    // the panel borders take two columns
+    (width as usize).saturating_sub(2 + indent).max(1)
+}
+
+// Long todos wrap below their text, the prefix hangs out on the left
+// Only the `shown` rows are listed, with the characters matching `query` highlighted
+fn show_todos(
+    list: &[Todo],
+    shown: &[usize],
+    selected: usize,
+    marked: &[usize],
+    query: Option<&str>,
+    width: u16,
+    theme: &Theme,
+) -> Vec<ListItem<'static>> {
+    let mut items = Vec::new();
+    let rows = todo::rows(list);
+
+    for &index in shown {
+        let row = &rows[index];
+        let prefix = prefix(row);
+        let indent = prefix.width();
+        let content = display_text(row.todo);
+        let matched = query
+            .and_then(|query| search::find(query, &content))
+            .unwrap_or_default();
+
+        let lines: Vec<Line> = wrap::wrap(&content, text_width(width, indent))
+            .into_iter()
+            .enumerate()
+            .map(|(number, range)| {
+                let lead = if number == 0 {
+                    prefix.clone()
+                } else {
+                    " ".repeat(indent)
+                };
+                let mut style = if row.todo.done {
+                    theme.completed
+                } else {
+                    theme.item
+                };
+                if marked.contains(&index) {
+                    style = style.patch(theme.marked);
+                }
+                if index == selected {
+                    style = style.patch(theme.selected);
+                }
+                let text = content[range.clone()].trim_end();
+                let mut spans = vec![Span::styled(lead, style)];
+                spans.extend(highlight(text, range.start, &matched, style, theme));
+                Line::from(spans)
+            })
+            .collect();
+        items.push(ListItem::new(lines));
+    }
+    items
+}
+
+// `text` starting at byte `start` of a todo, split into spans so the `matched` bytes stand out
+fn highlight(
+    text: &str,
+    start: usize,
+    matched: &[usize],
+    style: Style,
+    theme: &Theme,
+) -> Vec<Span<'static>> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut in_match = false;
+    for (index, c) in text.char_indices() {
+        let is_match = matched.contains(&(start + index));
+        if is_match != in_match && !run.is_empty() {
+            let run_style = if in_match {
+                style.patch(theme.matched)
+            } else {
+                style
+            };
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        in_match = is_match;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        let run_style = if in_match {
+            style.patch(theme.matched)
+        } else {
+            style
+        };
+        spans.push(Span::styled(run, run_style));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use super::App;
+    use crate::action::{self, Effect};
+    use crate::codec::{self, Kind, Section};
+    use crate::markdown;
+    use crate::state::{Confirm, Mode, Screen};
+    use crate::storage::{FileStorage, Memory};
+    use crate::todo::Todo;
+    use std::path::PathBuf;
+
+    fn serialize(state: &mut App, text: &str) {
+        let section = markdown::parse_body(text);
+        state.lists[state.tab].passthrough = section.passthrough;
+        state.lists[state.tab].below = section.below;
+        state.set_todos(section.todos);
+    }
+
+    fn deserialize(state: &App) -> String {
+        markdown::write_body(&state.current())
+    }
+
+    fn render(state: &App, doc: &str) -> String {
+        Kind::Markdown
+            .codec("## Todo")
+            .encode(doc, &[state.current()])
+    }
+
+    fn app() -> App {
+        App::new(Box::new(Memory::default()))
+    }
+
+    fn press(state: &mut App, keys: &str) -> Vec<Effect> {
+        action::keys(keys)
+            .into_iter()
+            .flat_map(|key| state.handle_key(key))
+            .collect()
+    }
+
+    fn file_app(path: PathBuf) -> App {
+        App::new(Box::new(FileStorage::new(
+            path,
+            Kind::Markdown.codec("## Todo"),
+        )))
+    }
+
+    #[test]
+    fn deserializer() {
+        let mut state = app();
+        state.active_todos = Vec::from([Todo::new(String::from("class"), false)]);
+        state.completed_todos = Vec::from([Todo::new(String::from("for"), true)]);
+
+        let result = String::from("- [x] for\n- [ ] class\n");
+        assert_eq!(deserialize(&state), result);
+    }
+
+    #[test]
+    fn serializer() {
+        let mut state = app();
+        let result = String::from("- [x] for\n- [ ] class\n");
+        serialize(&mut state, &result);
+
+        let mut reference = app();
+        reference.active_todos = Vec::from([Todo {
+            source: Some(String::from("- [ ] class")),
+            ..Todo::new(String::from("class"), false)
+        }]);
+        reference.completed_todos = Vec::from([Todo {
+            // above the first active todo in the file
+            origin: Some(0),
+            source: Some(String::from("- [x] for")),
+            ..Todo::new(String::from("for"), true)
+        }]);
+
+        assert_eq!(state.active_todos, reference.active_todos);
+        assert_eq!(state.completed_todos, reference.completed_todos);
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let doc = "# Project\n\nSome intro.\n\n## Todo\n- [x] first\n- [x] second\n- [ ] third\n\n## License\n\nMIT\n";
+        let mut state = app();
+        serialize(
+            &mut state,
+            &doc[markdown::find_section(doc, "## Todo").unwrap()],
+        );
+        assert_eq!(render(&state, doc), doc);
+    }
+
+    #[test]
+    fn save_replaces_section_contents_only() {
+        let doc = "intro\n## Todo\n- [ ] old\n### Notes\n- [ ] nested\n## Changelog\n- v0.1.0\n";
+        let mut state = app();
+        state.set_active_todos(Todo::new(String::from("new"), false));
+        state.set_completed_todos(Todo::new(String::from("done"), true));
+        assert_eq!(
+            render(&state, doc),
+            "intro\n## Todo\n- [x] done\n- [ ] new\n## Changelog\n- v0.1.0\n"
+        );
+    }
+
+    #[test]
+    fn save_appends_missing_section() {
+        let mut state = app();
+        state.set_active_todos(Todo::new(String::from("a"), false));
+        assert_eq!(
+            render(&state, "# Readme\n"),
+            "# Readme\n\n## Todo\n- [ ] a\n"
+        );
+    }
+
+    #[test]
+    fn serializer_keeps_other_lines() {
+        let mut state = app();
+        serialize(
+            &mut state,
+            "\nSome notes.\n* [X] done\n\n1. [ ] first\nmore notes\n\n",
+        );
+        assert_eq!(texts(&state.active_todos), ["first"]);
+        assert_eq!(texts(&state.completed_todos), ["done"]);
+        assert_eq!(
+            deserialize(&state),
+            "\nSome notes.\n* [X] done\n\n1. [ ] first\nmore notes\n"
+        );
+
+        // they stay next to the todos around them
+        let text = "- [ ] a\n  note about a\n- [ ] b\n### Later\n- [ ] c\n";
+        serialize(&mut state, text);
+        press(&mut state, "m");
+        state.completed_todos[0].completed = None;
+        assert_eq!(
+            deserialize(&state),
+            "- [x] a\n  note about a\n- [ ] b\n### Later\n- [ ] c\n"
+        );
+        let new = state.new_todo();
+        state.active_todos.push(Todo {
+            text: String::from("d"),
+            created: None,
+            ..new
+        });
+        assert_eq!(
+            deserialize(&state),
+            "- [x] a\n  note about a\n- [ ] b\n### Later\n- [ ] c\n- [ ] d\n"
+        );
+
+        // and move with the todo above them
+        serialize(&mut state, "- [ ] a\n- [ ] b\n  note about b\n- [ ] c\n");
+        press(&mut state, "jgk<Enter>");
+        assert_eq!(
+            deserialize(&state),
+            "- [ ] b\n  note about b\n- [ ] a\n- [ ] c\n"
+        );
+
+        // code blocks hold no todos
+        serialize(&mut state, "- [ ] a\n```\n- [ ] x\n```\n");
+        press(&mut state, "m");
+        state.completed_todos[0].completed = None;
+        assert_eq!(deserialize(&state), "- [x] a\n```\n- [ ] x\n```\n");
+    }
+
+    #[test]
+    fn broken_checkboxes_still_load() {
+        let path = std::env::temp_dir().join(format!("ratatatui-{}-parse.md", std::process::id()));
+        let doc = "# Title\n\n## Todo\n- [ ] ok\n- [WIP rewrite\n";
+        std::fs::write(&path, doc).unwrap();
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        assert_eq!(texts(&state.active_todos), ["ok"]);
+        state.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), doc);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn nested_round_trip() {
+        let text = "- [x] shipped\n  - [x] code\n  - [ ] docs\n- [ ] next\n  - [ ] plan\n    - [x] research\n";
+        let mut state = app();
+        serialize(&mut state, text);
+        assert_eq!(
+            state.active_todos[0].children[0].children[0].text,
+            "research"
+        );
+        assert!(!state.completed_todos[0].children[1].done);
+        assert_eq!(deserialize(&state), text);
+    }
+
+    #[test]
+    fn completing_parent_asks_about_children() {
+        let mut state = app();
+        serialize(&mut state, "- [ ] parent\n  - [ ] child\n- [ ] other\n");
+
+        state.complete_selected();
+        assert_eq!(state.state.mode, Mode::CONFIRM);
+        assert_eq!(state.active_todos.len(), 2);
+
+        state.finish_complete(true);
+        assert_eq!(state.active_todos.len(), 1);
+        assert!(state.completed_todos[0].children[0].done);
+        assert!(state.completed_todos[0].children[0].completed.is_some());
+        assert_eq!(state.active_todos[0].text, "other");
+    }
+
+    #[test]
+    fn completing_subtask_keeps_it_in_place() {
+        let mut state = app();
+        serialize(&mut state, "- [ ] parent\n  - [ ] child\n");
+        state.active_selected = 1;
+        state.complete_selected();
+        assert_eq!(state.state.mode, Mode::READ);
+        assert!(state.active_todos[0].children[0].done);
+
+        // and back again
+        state.complete_selected();
+        assert!(!state.active_todos[0].children[0].done);
+        assert_eq!(state.active_selected, 1);
+    }
+
+    #[test]
+    fn load_keeps_metadata_and_assigns_ids() {
+        let path = std::env::temp_dir().join(format!("ratatatui-{}-meta.md", std::process::id()));
+        std::fs::write(
+            &path,
+            "## Todo\n- [ ] tagged <!-- id:4 pri:A tags:home -->\n  - [ ] plain\n- [ ] copy <!-- id:4 -->\n- [x] old\n",
+        )
+        .unwrap();
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(state.active_todos[0].priority, Some('A'));
+        assert_eq!(state.active_todos[0].children[0].id, 5);
+        // the later of two todos with the same id gets a new one
+        assert_eq!(state.active_todos[1].id, 6);
+        assert_eq!(state.completed_todos[0].id, 7);
+        assert_eq!(state.new_todo().id, 8);
+        assert_eq!(
+            deserialize(&state),
+            "- [ ] tagged <!-- id:4 pri:A tags:home -->\n  - [ ] plain\n- [ ] copy <!-- id:6 -->\n- [x] old\n"
+        );
+    }
+
+    fn todos(texts: &[&str], done: bool) -> Vec<Todo> {
+        texts
+            .iter()
+            .map(|text| Todo::new(text.to_string(), done))
+            .collect()
+    }
+
+    fn texts(list: &[Todo]) -> Vec<&str> {
+        list.iter().map(|todo| todo.text.as_str()).collect()
+    }
+
+    #[test]
+    fn complete_keeps_selections_on_their_rows() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b", "c"], false);
+        state.completed_todos = todos(&["x", "y"], true);
+        state.active_selected = 2;
+        state.completed_selected = 1;
+
+        state.complete_selected();
+        assert_eq!(texts(&state.active_todos), vec!["a", "b"]);
+        assert_eq!(texts(&state.completed_todos), vec!["c", "x", "y"]);
+        assert_eq!(state.active_selected, 1);
+        // still on "y"
+        assert_eq!(state.completed_selected, 2);
+    }
+
+    #[test]
+    fn reopen_restores_original_position() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b", "c"], false);
+        state.active_selected = 1;
+        state.complete_selected();
+        assert_eq!(state.active_selected, 1);
+        assert_eq!(state.completed_todos[0].origin, Some(1));
+
+        state.state.active = Screen::COMPLETED;
+        state.complete_selected();
+        assert_eq!(texts(&state.active_todos), vec!["a", "b", "c"]);
+        assert!(!state.active_todos[1].done);
+        assert_eq!(state.active_todos[1].completed, None);
+        // the selection follows "c", which moved down a row
+        assert_eq!(state.active_selected, 2);
+        assert!(state.completed_todos.is_empty());
+        assert_eq!(state.completed_selected, 0);
+    }
+
+    #[test]
+    fn reopen_without_origin_appends() {
+        let mut state = app();
+        state.active_todos = todos(&["a"], false);
+        state.completed_todos = todos(&["x", "y"], true);
+        state.state.active = Screen::COMPLETED;
+        state.completed_selected = 1;
+
+        state.reopen_selected();
+        assert_eq!(texts(&state.active_todos), vec!["a", "y"]);
+        assert_eq!(state.active_selected, 0);
+        assert_eq!(state.completed_selected, 0);
+    }
+
+    #[test]
+    fn delete_clamps_selection() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b"], false);
+        state.active_todos[1].children = todos(&["b.1"], false);
+        state.active_selected = 1;
+
+        state.delete_selected();
+        assert_eq!(texts(&state.active_todos), vec!["a"]);
+        assert_eq!(state.active_selected, 0);
+        state.delete_selected();
+        assert!(state.active_todos.is_empty());
+        assert_eq!(state.active_selected, 0);
+        // nothing left to delete
+        state.delete_selected();
+
+        state.state.active = Screen::COMPLETED;
+        state.completed_todos = todos(&["x", "y", "z"], true);
+        state.completed_selected = 1;
+        state.delete_selected();
+        assert_eq!(texts(&state.completed_todos), vec!["x", "z"]);
+        assert_eq!(state.completed_selected, 1);
+    }
+
+    #[test]
+    fn undo_and_redo_mutations() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b"], false);
+
+        state.complete_selected();
+        state.state.active = Screen::COMPLETED;
+        state.delete_selected();
+        assert!(state.completed_todos.is_empty());
+
+        state.undo();
+        assert_eq!(texts(&state.completed_todos), vec!["a"]);
+        state.undo();
+        assert_eq!(texts(&state.active_todos), vec!["a", "b"]);
+        assert!(state.completed_todos.is_empty());
+        // nothing left to undo
+        state.undo();
+        assert_eq!(texts(&state.active_todos), vec!["a", "b"]);
+
+        state.redo();
+        state.redo();
+        assert_eq!(texts(&state.active_todos), vec!["b"]);
+        assert!(state.completed_todos.is_empty());
+        assert_eq!(state.history.get(), "UNDO: delete (2)  REDO: -");
+    }
+
+    #[test]
+    fn failed_indent_is_not_recorded() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b"], false);
+        state.reparent(crate::todo::outdent);
+        assert_eq!(state.history.get(), "UNDO: -  REDO: -");
+        state.active_selected = 1;
+        state.reparent(crate::todo::indent);
+        assert_eq!(state.history.get(), "UNDO: indent (1)  REDO: -");
+    }
+
+    #[test]
+    fn emptied_completed_todo_is_removed() {
+        let mut state = app();
+        state.completed_todos = todos(&["x", "y"], true);
+        state.completed_todos[0].children = todos(&["x.1"], true);
+        state.state.active = Screen::COMPLETED;
+        state.state.mode = Mode::EDIT;
+
+        state.selected_todo().unwrap().text.clear();
+        state.finish_edit();
+        assert_eq!(state.state.mode, Mode::READ);
+        assert_eq!(texts(&state.completed_todos), vec!["x.1", "y"]);
+        assert!(state.active_todos.is_empty());
+    }
+
+    #[test]
+    fn cursor_follows_prefix_and_editor() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b"], false);
+        state.active_todos[1].children = todos(&["émoji 👍"], false);
+        state.active_todos[1].children[0].priority = Some('A');
+        assert_eq!(state.cursor(), None);
+
+        state.active_selected = 2;
+        state.begin_edit();
+        // "  " indent, "  " fold, "[ ] ", "(A) " and the text
+        assert_eq!(state.cursor(), Some((2, 12 + 8)));
+        state.editor.backspace();
+        assert_eq!(state.cursor(), Some((2, 12 + 6)));
+    }
+
+    #[test]
+    fn paging_stays_in_the_list() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b", "c", "d", "e", "f", "g"], false);
+        // panel of 5 rows, 3 inside the borders
+        state.update_len(5, 5, 5);
+
+        state.page(true);
+        assert_eq!(state.active_selected, 3);
+        state.page(true);
+        assert_eq!(state.active_selected, 6);
+        state.page(false);
+        assert_eq!(state.active_selected, 3);
+        state.page(false);
+        state.page(false);
+        assert_eq!(state.active_selected, 0);
+    }
+
+    #[test]
+    fn long_todos_wrap_with_hanging_indent() {
+        let mut state = app();
+        state.active_todos = todos(&["short", "buy milk and eggs"], false);
+        // 2 border columns, 6 prefix columns and 9 for the text
+        state.update_width(17, 17);
+        state.active_selected = 1;
+
+        let items = state.show_active_todos();
+        assert_eq!(
+            items.iter().map(|i| i.height()).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(state.heights(), vec![1, 2]);
+
+        state.begin_edit();
+        assert_eq!(state.cursor(), Some((2, 6 + 8)));
+        state.editor.handle(crossterm::event::KeyEvent::from(
+            crossterm::event::KeyCode::Home,
+        ));
+        assert_eq!(state.cursor(), Some((1, 6)));
+
+        // the selected todo scrolled to the top of the panel
+        state.active_offset = 1;
+        assert_eq!(state.cursor(), Some((0, 6)));
+    }
+
+    #[test]
+    fn paging_counts_wrapped_lines() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "buy milk and eggs", "c", "d"], false);
+        state.update_width(17, 17);
+        // 3 lines inside the borders
+        state.update_len(5, 5, 5);
+        state.page(true);
+        assert_eq!(state.active_selected, 2);
+        state.page(true);
+        assert_eq!(state.active_selected, 3);
+        state.page(false);
+        assert_eq!(state.active_selected, 1);
+    }
+
+    #[test]
+    fn save_keeps_backups() {
+        let dir = std::env::temp_dir().join(format!("ratatatui-{}-save", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("readme.md");
+        std::fs::write(&path, "# Title\n\n## Todo\n- [ ] old\n").unwrap();
+
+        let mut storage = FileStorage::new(path.clone(), Kind::Markdown.codec("## Todo"));
+        storage.backups = 1;
+        let mut state = App::new(Box::new(storage));
+        state.load().unwrap();
+        state.active_todos[0].text = String::from("new");
+        state.save().unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let backup = std::fs::read_to_string(crate::file::backup_path(&path, 0)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(saved.contains("- [ ] new"));
+        assert_eq!(backup, "# Title\n\n## Todo\n- [ ] old\n");
+    }
+
+    #[test]
+    fn save_reports_errors() {
+        let path = std::env::temp_dir()
+            .join(format!("ratatatui-{}-missing", std::process::id()))
+            .join("readme.md");
+        let mut state = file_app(path);
+        state.set_active_todos(Todo::new(String::from("a"), false));
+        assert!(state.save().is_err());
+        assert!(state.is_dirty());
+    }
+
+    #[test]
+    fn commands_save_and_quit() {
+        let dir = std::env::temp_dir().join(format!("ratatatui-{}-command", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("readme.md");
+        std::fs::write(&path, "## Todo\n- [ ] a <!-- id:1 -->\n").unwrap();
+
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        assert!(!state.is_dirty());
+        state.active_todos[0].text = String::from("b");
+        assert!(state.is_dirty());
+
+        // unsaved changes keep :q from quitting
+        assert!(!state.run_command("q"));
+        assert!(state.message.is_some());
+        assert!(!state.run_command("w"));
+        assert!(!state.is_dirty());
+        assert!(state.run_command("q"));
+
+        state.active_todos[0].text = String::from("c");
+        assert!(state.run_command("q!"));
+        assert!(!state.run_command("nope"));
+        assert_eq!(state.message.as_deref(), Some("Unknown command :nope"));
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, "## Todo\n- [ ] b <!-- id:1 -->\n");
+
+        // quitting without changes does not write the file
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(state.save_and_quit());
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn folding_is_not_a_change() {
+        let mut state = app();
+        serialize(&mut state, "- [ ] a\n  - [ ] b\n");
+        state.saved = codec::stored(&state.sections());
+        state.active_todos[0].collapsed = true;
+        assert!(!state.is_dirty());
+    }
+
+    #[test]
+    fn changes_on_disk_are_noticed() {
+        let storage = Memory::default();
+        storage.set(vec![markdown::parse_body("- [ ] a <!-- id:1 -->\n")]);
+        let mut state = App::new(Box::new(storage.clone()));
+        state.load().unwrap();
+        state.watch();
+        assert_eq!(state.confirm, None);
+
+        storage.set(vec![markdown::parse_body("- [ ] b <!-- id:1 -->\n")]);
+        // quitting does not overwrite it with our changes
+        state.active_todos[0].text = String::from("a ours");
+        assert!(!state.save_and_quit());
+        assert_eq!(state.confirm, Some(Confirm::Conflict));
+
+        // once ignored it is not asked about again
+        state.state.mode = Mode::READ;
+        state.confirm = None;
+        state.ignored = state.changed_on_disk();
+        state.watch();
+        assert_eq!(state.confirm, None);
+
+        state.reload().unwrap();
+        assert_eq!(texts(&state.active_todos), ["b"]);
+        assert!(!state.is_dirty());
+        assert!(state.changed_on_disk().is_none());
+        state.undo();
+        assert_eq!(texts(&state.active_todos), ["a ours"]);
+        // saving takes our todos back over
+        state.save().unwrap();
+        assert_eq!(texts(&storage.get().unwrap()[0].todos), ["a ours"]);
+    }
+
+    #[test]
+    fn merge_with_changes_on_disk() {
+        let dir = std::env::temp_dir().join(format!("ratatatui-{}-merge", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("readme.md");
+        std::fs::write(&path, "## Todo\n- [ ] a\n- [ ] b\n").unwrap();
+
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        state.active_todos[0].text = String::from("a ours");
+        // someone else completes b and adds c, without ids
+        std::fs::write(&path, "## Todo\n- [x] b\n- [ ] a\n- [ ] c\n").unwrap();
+
+        state.merge().unwrap();
+        assert_eq!(texts(&state.active_todos), ["a ours", "c"]);
+        assert_eq!(texts(&state.completed_todos), ["b"]);
+        assert_eq!(state.active_todos[1].id, 3);
+        assert!(state.changed_on_disk().is_none());
+        state.save().unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved, "## Todo\n- [ ] a ours\n- [ ] c\n- [x] b\n");
+    }
+
+    #[test]
+    fn export_and_import_commands() {
+        let path = PathBuf::from("todos.jsonl");
+        let storage = Memory::default();
+        let mut state = App::new(Box::new(storage.clone()));
+        serialize(
+            &mut state,
+            "- [x] a <!-- id:1 -->\n- [ ] b <!-- id:2 -->\n  - [ ] c <!-- id:3 -->\n",
+        );
+
+        assert!(!state.run_command(&format!("export {}", path.display())));
+        assert_eq!(
+            state.message,
+            Some(format!("Exported 3 todos to {}", path.display()))
+        );
+        assert_eq!(storage.exported(&path).unwrap().lines().count(), 3);
+
+        state.set_todos(Vec::new());
+        state.run_command(&format!("import {}", path.display()));
+        assert_eq!(texts(&state.completed_todos), ["a"]);
+        assert_eq!(texts(&state.active_todos), ["b"]);
+        assert_eq!(state.active_todos[0].children[0].id, 3);
+
+        // undo brings back what was there before
+        state.undo();
+        assert!(state.active_todos.is_empty());
+
+        state.run_command("import /nonexistent.json");
+        assert!(state.message.unwrap().starts_with("Import failed"));
+    }
+
+    #[test]
+    fn import_appends_with_new_ids() {
+        let mut state = app();
+        serialize(&mut state, "- [ ] a <!-- id:1 -->\n");
+        state.assign_ids();
+        let imported = vec![Todo {
+            id: 1,
+            ..Todo::new(String::from("b"), false)
+        }];
+        state.import(
+            vec![Section {
+                todos: imported,
+                ..Default::default()
+            }],
+            true,
+        );
+        assert_eq!(texts(&state.active_todos), ["a", "b"]);
+        assert_eq!(state.active_todos[1].id, 2);
+    }
+
+    #[test]
+    fn todo_txt_files() {
+        let path = std::env::temp_dir().join(format!("ratatatui-{}-todo.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            "(A) call mom +family @phone due:2024-05-03\nx 2024-05-02 2024-05-01  pay rent pri:B\n",
+        )
+        .unwrap();
+        let mut state = App::new(Box::new(FileStorage::new(
+            path.clone(),
+            Kind::TodoTxt.codec("## Todo"),
+        )));
+        state.load().unwrap();
+        assert_eq!(state.active_todos[0].tags, ["family", "@phone"]);
+        assert!(!state.is_dirty());
+
+        state.active_todos[0].text.push_str(" today");
+        state.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            saved,
+            "(A) call mom +family @phone due:2024-05-03 today\nx 2024-05-02 2024-05-01  pay rent pri:B\n"
+        );
+    }
+
+    #[test]
+    fn lists_are_tabs() {
+        let path = std::env::temp_dir().join(format!("ratatatui-{}-lists.md", std::process::id()));
+        std::fs::write(
+            &path,
+            "# Project\n## Backlog\n- [ ] a\n- [ ] b\n## Bugs\n- [x] fixed\n",
+        )
+        .unwrap();
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        assert_eq!(state.list_names(), ["Backlog", "Bugs"]);
+        assert_eq!(texts(&state.active_todos), ["a", "b"]);
+
+        state.move_to_list(true);
+        assert_eq!(texts(&state.active_todos), ["b"]);
+        state.switch_list(true);
+        assert_eq!(state.tab, 1);
+        assert_eq!(texts(&state.active_todos), ["a"]);
+        assert_eq!(texts(&state.completed_todos), ["fixed"]);
+
+        // undo goes back to the list the todo was moved from
+        state.undo();
+        assert_eq!(state.tab, 0);
+        assert_eq!(texts(&state.active_todos), ["a", "b"]);
+        state.redo();
+        assert!(state.is_dirty());
+
+        state.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            saved,
+            "# Project\n## Backlog\n- [ ] b\n## Bugs\n- [x] fixed\n- [ ] a\n"
+        );
+    }
+
+    #[test]
+    fn switching_lists_changes_nothing() {
+        let path = std::env::temp_dir().join(format!("ratatatui-{}-tabs.md", std::process::id()));
+        let doc = "## Backlog\n- [ ] a\n- [x] b\n- [ ] c\n## Bugs\n- [x] d\n- [ ] e\n- [x] f\n";
+        std::fs::write(&path, doc).unwrap();
+        let mut state = file_app(path.clone());
+        state.load().unwrap();
+        state.switch_list(true);
+        assert!(!state.is_dirty());
+        state.switch_list(true);
+        assert!(!state.is_dirty());
+        state.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved, doc);
+    }
+
+    #[test]
+    fn visual_mode_selects_several_todos() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b", "c", "d", "e"], false);
+        press(&mut state, "jvj");
+        assert_eq!(state.state.mode, Mode::VISUAL);
+        assert_eq!(state.selection(), [1, 2]);
+        // space takes the todo under the cursor out of the range, or adds it
+        press(&mut state, " ");
+        assert_eq!(state.selection(), [1]);
+        press(&mut state, "jj ");
+        assert_eq!(state.selection(), [1, 4]);
+
+        // selected todos move on their own, and stop at the top
+        press(&mut state, "K");
+        assert_eq!(texts(&state.active_todos), ["b", "a", "c", "e", "d"]);
+        press(&mut state, "K");
+        assert_eq!(texts(&state.active_todos), ["b", "a", "e", "c", "d"]);
+        assert_eq!((state.active_selected, state.selection()), (2, vec![0, 2]));
+
+        press(&mut state, "m");
+        assert_eq!(state.state.mode, Mode::READ);
+        assert_eq!(texts(&state.active_todos), ["a", "c", "d"]);
+        assert_eq!(texts(&state.completed_todos), ["b", "e"]);
+        assert_eq!(state.message.as_deref(), Some("Completed 2 todo(s)"));
+
+        // and back, on the completed screen
+        press(&mut state, "<Tab>vjm");
+        assert_eq!(texts(&state.active_todos), ["b", "a", "e", "c", "d"]);
+        assert!(state.active_todos.iter().all(|todo| !todo.done));
+
+        press(&mut state, "<Tab>ggvjjd");
+        assert_eq!(state.confirm, Some(Confirm::DeleteSelection(3)));
+        press(&mut state, "y");
+        assert_eq!(texts(&state.active_todos), ["c", "d"]);
+        press(&mut state, "u");
+        assert_eq!(texts(&state.active_todos), ["b", "a", "e", "c", "d"]);
+
+        // a selection made while searching only takes the todos found
+        press(&mut state, "/a<Enter>ggvG");
+        assert_eq!(state.selection(), [1]);
+        press(&mut state, "<Esc>");
+        assert_eq!(state.state.mode, Mode::READ);
+    }
+
+    #[test]
+    fn visual_mode_moves_todos_to_lists() {
+        let mut state = app();
+        state.lists.push(Section {
+            name: String::from("## Bugs"),
+            ..Default::default()
+        });
+        state.active_todos = todos(&["a", "b", "c"], false);
+        press(&mut state, "vj}");
+        assert_eq!(texts(&state.active_todos), ["c"]);
+        assert_eq!(state.message.as_deref(), Some("Moved 2 todo(s) to Bugs"));
+        press(&mut state, "]");
+        assert_eq!(texts(&state.active_todos), ["a", "b"]);
+    }
+
+    #[test]
+    fn keys_insert_edit_and_undo() {
+        let mut state = app();
+        press(&mut state, "ia<Enter>b<Enter><Esc>");
+        assert_eq!(texts(&state.active_todos), ["a", "b"]);
+        assert_eq!(state.state.mode, Mode::READ);
+
+        press(&mut state, "kex<Enter>");
+        assert_eq!(texts(&state.active_todos), ["ax", "b"]);
+        press(&mut state, "u");
+        assert_eq!(texts(&state.active_todos), ["a", "b"]);
+        press(&mut state, "<C-r>");
+        assert_eq!(texts(&state.active_todos), ["ax", "b"]);
+
+        // leaving edit or grab without a change keeps what can be redone
+        press(&mut state, "ue<Esc>g<Esc><C-r>");
+        assert_eq!(texts(&state.active_todos), ["ax", "b"]);
+    }
+
+    #[test]
+    fn keys_grab_and_jump() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b", "c"], false);
+        press(&mut state, "gj");
+        assert_eq!(state.state.mode, Mode::GRAB);
+        assert_eq!(state.pending_key, None);
+        press(&mut state, "j<Esc>");
+        assert_eq!(texts(&state.active_todos), ["b", "c", "a"]);
+        assert_eq!(state.state.mode, Mode::READ);
+
+        press(&mut state, "gg");
+        assert_eq!(state.active_selected, 0);
+        press(&mut state, "G");
+        assert_eq!(state.active_selected, 2);
+        press(&mut state, "u");
+        assert_eq!(texts(&state.active_todos), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn search_filters_both_screens() {
+        let mut state = app();
+        serialize(
+            &mut state,
+            "- [ ] buy milk\n- [ ] release\n  - [ ] write docs\n- [ ] ship docs\n- [x] old docs\n- [x] taxes\n",
+        );
+        press(&mut state, "/dcs");
+        assert_eq!(state.state.mode, Mode::SEARCH);
+        // subtasks are shown with the todos they belong to, the selection follows the typing
+        assert_eq!(state.shown_on(Screen::ACTIVE), [1, 2, 3]);
+        assert_eq!(state.active_selected, 2);
+        assert_eq!(state.shown_on(Screen::COMPLETED), [1]);
+
+        press(&mut state, "<Enter>");
+        assert_eq!(state.search.as_deref(), Some("dcs"));
+        press(&mut state, "j");
+        assert_eq!(state.active_selected, 3);
+        press(&mut state, "jj");
+        assert_eq!(state.active_selected, 2);
+        press(&mut state, "N");
+        assert_eq!(state.active_selected, 3);
+        press(&mut state, "n");
+        assert_eq!(state.active_selected, 2);
+
+        // actions work on the todo shown, not on the row at the same place of the whole list
+        press(&mut state, "G");
+        assert_eq!(state.shown_selected(Screen::ACTIVE), 2);
+        press(&mut state, "m");
+        assert_eq!(
+            texts(&state.completed_todos),
+            ["ship docs", "taxes", "old docs"]
+        );
+        assert_eq!(state.show_active_todos().len(), 2);
+
+        press(&mut state, "/xyz<Enter>n");
+        assert_eq!(state.message.as_deref(), Some("No matches for xyz"));
+        // Esc clears the search while typing it and after
+        press(&mut state, "/<Esc>");
+        assert_eq!(state.search, None);
+        press(&mut state, "/b<Enter>");
+        assert_eq!(state.show_active_todos().len(), 1);
+        press(&mut state, "<Esc>");
+        assert_eq!(state.search, None);
+        assert_eq!(state.show_active_todos().len(), 3);
+    }
+
+    #[test]
+    fn priority_on_nothing_is_not_undoable() {
+        let mut state = app();
+        press(&mut state, "ia<Esc>pu");
+        assert_eq!(state.active_todos[0].priority, None);
+        // the redo of the priority survives pressing p where nothing is selected
+        press(&mut state, "<Tab>p<Tab><C-r>");
+        assert_eq!(state.active_todos[0].priority, Some('A'));
+        press(&mut state, "u");
+        assert_eq!(texts(&state.active_todos), ["a"]);
+    }
+
+    #[test]
+    fn keys_answer_questions() {
+        let mut state = app();
+        state.active_todos = todos(&["a", "b"], false);
+        press(&mut state, "m<Tab>");
+        assert_eq!(state.state.active, Screen::COMPLETED);
+        assert_eq!(texts(&state.completed_todos), ["a"]);
+
+        press(&mut state, "d");
+        assert_eq!(state.confirm, Some(Confirm::Delete(String::from("a"), 0)));
+        // other keys wait for an answer
+        press(&mut state, "jx");
+        assert_eq!(state.state.mode, Mode::CONFIRM);
+        press(&mut state, "n");
+        assert_eq!(texts(&state.completed_todos), ["a"]);
+        press(&mut state, "dy");
+        assert!(state.completed_todos.is_empty());
+        assert_eq!(state.state.mode, Mode::READ);
+    }
+
+    #[test]
+    fn keys_quit() {
+        let storage = Memory::default();
+        let mut state = App::new(Box::new(storage.clone()));
+        assert!(press(&mut state, "ia<Esc>:x<Esc>").is_empty());
+        assert_eq!(press(&mut state, ":q<Enter>"), []);
+        assert!(state
+            .message
+            .as_ref()
+            .unwrap()
+            .starts_with("Unsaved changes"));
+
+        assert_eq!(press(&mut state, "q"), [Effect::Quit]);
+        assert_eq!(texts(&storage.get().unwrap()[0].todos), ["a"]);
+        press(&mut state, "ib<Esc>");
+        assert_eq!(press(&mut state, ":q!<Enter>"), [Effect::Quit]);
     }
 }
@@ -0,0 +1,569 @@
+use crate::app::App;
+use crate::codec::Section;
+use crate::export::{self, Format};
+use crate::todo::{self, Todo};
+use crate::todotxt;
+use clap::{Args, Subcommand};
+use serde::Serialize;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+// Subcommands that work on the todo file without the TUI, for git hooks, shell aliases and
+// Makefiles. Todos are addressed by the ids `list` prints, whichever list of the file they are
+// in. Every command prints the todos it listed or changed, one per line under the heading of
+// their list when the file has several, or as a JSON array with --json. The ids of those todos
+// are written to the file, so they are the same the next time.
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Add a todo at the end of the list opened first (see --section) and print it
+    Add {
+        /// Text of the todo
+        #[arg(required = true)]
+        text: Vec<String>,
+
+        /// Priority from A (highest) to Z
+        #[arg(short, long)]
+        priority: Option<char>,
+
+        /// Tag the todo, can be given several times
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Add it as the last subtask of this todo
+        #[arg(short, long, value_name = "ID")]
+        under: Option<u64>,
+    },
+    /// Print the todos with their ids
+    List {
+        /// Include completed todos
+        #[arg(short, long)]
+        all: bool,
+    },
+    /// Mark todos as done
+    Done {
+        #[arg(required = true)]
+        ids: Vec<u64>,
+
+        /// Complete their subtasks too
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Mark todos as not done
+    Undone {
+        #[arg(required = true)]
+        ids: Vec<u64>,
+    },
+    /// Delete todos together with their subtasks
+    Rm {
+        #[arg(required = true)]
+        ids: Vec<u64>,
+    },
+    /// Move a todo together with its subtasks
+    Move {
+        id: u64,
+
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Write the todos of every list as JSON or JSON Lines, top level todos name their list
+    Export {
+        /// File to write, standard output if not given
+        file: Option<PathBuf>,
+
+        /// Format, taken from the file extension (.jsonl) if not given
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Replace the todos of each list named in a JSON or JSON Lines export, todos of lists the
+    /// file does not have go to the list opened first (see --section)
+    Import {
+        /// File to read, - for standard input
+        file: PathBuf,
+
+        /// Format, taken from the file extension (.jsonl) if not given
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+
+        /// Add them after the current todos, with new ids
+        #[arg(short, long)]
+        append: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+pub struct Target {
+    /// Right before this todo
+    #[arg(long, value_name = "ID")]
+    before: Option<u64>,
+
+    /// Right after this todo
+    #[arg(long, value_name = "ID")]
+    after: Option<u64>,
+
+    /// As the last subtask of this todo
+    #[arg(long, value_name = "ID")]
+    under: Option<u64>,
+
+    /// To the end of the top level list
+    #[arg(long)]
+    top: bool,
+}
+
+// A todo as it is printed
+#[derive(Serialize, Debug, PartialEq)]
+struct Item {
+    id: u64,
+    text: String,
+    done: bool,
+    priority: Option<char>,
+    tags: Vec<String>,
+    created: Option<String>,
+    completed: Option<String>,
+    // the todo this is a subtask of
+    parent: Option<u64>,
+    depth: usize,
+    // name of its list, the heading for markdown
+    list: String,
+}
+
+impl Item {
+    fn line(&self) -> String {
+        let mark = if self.done { 'x' } else { ' ' };
+        let mut line = format!("{:>4} {}[{}] ", self.id, "  ".repeat(self.depth), mark);
+        if let Some(priority) = self.priority {
+            line += &format!("({}) ", priority);
+        }
+        line += &self.text;
+        for tag in &self.tags {
+            if !todotxt::in_text(&self.text, tag) {
+                line += &format!(" #{}", tag);
+            }
+        }
+        line
+    }
+}
+
+// Run `command` on the loaded todos of `app`, returns what to print. Saving is left to the
+// caller, whenever `app` is dirty afterwards.
+pub fn run(app: &mut App, command: Command, json: bool) -> io::Result<String> {
+    let mut lists = app.sections();
+    let first = app.tab;
+    let mut text = None;
+    let items = match command {
+        Command::Add {
+            text,
+            priority,
+            tags,
+            under,
+        } => {
+            let mut todo = app.new_todo();
+            todo.text = text.join(" ");
+            todo.priority = match priority.map(|p| p.to_ascii_uppercase()) {
+                Some(p) if !p.is_ascii_uppercase() => {
+                    return Err(invalid(format!("priority {} is not a letter", p)))
+                }
+                p => p,
+            };
+            todo.tags = tags;
+            let id = todo.id;
+            match under {
+                Some(parent) => find(&mut lists, parent)?.children.push(todo),
+                None => lists[first].todos.push(todo),
+            }
+            select(&lists, &[id])
+        }
+        Command::List { all } => {
+            let mut items = items(&lists);
+            items.retain(|item| all || !item.done);
+            items
+        }
+        Command::Done { ids, recursive } => {
+            for &id in &ids {
+                let todo = find(&mut lists, id)?;
+                if recursive {
+                    todo.set_done_recursive(true);
+                } else {
+                    todo.set_done(true);
+                }
+            }
+            select(&lists, &ids)
+        }
+        Command::Undone { ids } => {
+            for &id in &ids {
+                find(&mut lists, id)?.set_done(false);
+            }
+            select(&lists, &ids)
+        }
+        Command::Rm { ids } => {
+            for &id in &ids {
+                find(&mut lists, id)?;
+            }
+            let removed = select(&lists, &ids);
+            for &id in &ids {
+                // a todo may be gone already with its parent
+                if let Ok((list, path)) = path(&lists, id) {
+                    todo::remove(&mut lists[list].todos, &path);
+                }
+            }
+            removed
+        }
+        Command::Move { id, target } => {
+            move_todo(&mut lists, id, target)?;
+            select(&lists, &[id])
+        }
+        Command::Export { file, format } => {
+            let format = format.unwrap_or_else(|| file.as_deref().map_or(Format::Json, Format::of));
+            let exported = app.export(format);
+            text = Some(match file {
+                Some(file) => app
+                    .storage
+                    .export(&file, &exported)
+                    .map(|()| String::new())?,
+                None => exported,
+            });
+            items(&lists)
+        }
+        Command::Import {
+            file,
+            format,
+            append,
+        } => {
+            let text = if file.as_os_str() == "-" {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                app.storage.import(&file)?
+            };
+            let imported = export::import(&text, format.unwrap_or(Format::of(&file)))?;
+            app.import(imported, append);
+            lists = app.sections();
+            items(&lists)
+        }
+    };
+    // ids the user has seen stay the same the next time
+    for list in &mut lists {
+        todo::for_each_mut(&mut list.todos, &mut |todo| {
+            if items.iter().any(|item| item.id == todo.id) {
+                todo.id_in_file = true;
+            }
+        });
+    }
+    let count = lists.len();
+    app.show_list(lists, first);
+    Ok(text.unwrap_or_else(|| output(&items, count, json)))
+}
+
+// Todos can be moved to another list by naming a todo there
+fn move_todo(lists: &mut [Section], id: u64, target: Target) -> io::Result<()> {
+    let (list, from) = path(lists, id)?;
+    let other = target.before.or(target.after).or(target.under);
+    if let Some(other) = other {
+        let (other_list, to) = path(lists, other)?;
+        if other_list == list && to.starts_with(&from) {
+            return Err(invalid(format!(
+                "todo {} cannot be moved next to or under itself",
+                id
+            )));
+        }
+    }
+
+    let todo = todo::remove(&mut lists[list].todos, &from);
+    match (other, target.under) {
+        (_, Some(parent)) => find(lists, parent)?.children.push(todo),
+        (Some(other), None) => {
+            let (other_list, to) = path(lists, other)?;
+            let index = to.last().unwrap() + usize::from(target.after.is_some());
+            todo::siblings_mut(&mut lists[other_list].todos, &to).insert(index, todo);
+        }
+        (None, None) => lists[list].todos.push(todo),
+    }
+    Ok(())
+}
+
+// Index of the list holding the todo and its path in there
+fn path(lists: &[Section], id: u64) -> io::Result<(usize, Vec<usize>)> {
+    lists
+        .iter()
+        .enumerate()
+        .find_map(|(index, list)| todo::find_id(&list.todos, id).map(|path| (index, path)))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no todo with id {}", id)))
+}
+
+fn find(lists: &mut [Section], id: u64) -> io::Result<&mut Todo> {
+    let (list, path) = path(lists, id)?;
+    Ok(todo::get_mut(&mut lists[list].todos, &path).unwrap())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+// Items of the todos with the given ids, in file order
+fn select(lists: &[Section], ids: &[u64]) -> Vec<Item> {
+    let mut items = items(lists);
+    items.retain(|item| ids.contains(&item.id));
+    items
+}
+
+// Items of every todo, in file order
+fn items(lists: &[Section]) -> Vec<Item> {
+    let mut items = Vec::new();
+    for list in lists {
+        collect(&list.todos, &list.name, None, 0, &mut items);
+    }
+    items
+}
+
+fn collect(todos: &[Todo], list: &str, parent: Option<u64>, depth: usize, items: &mut Vec<Item>) {
+    for todo in todos {
+        items.push(Item {
+            id: todo.id,
+            text: todo.text.clone(),
+            done: todo.done,
+            priority: todo.priority,
+            tags: todo.tags.clone(),
+            created: todo.created.map(todo::format_time),
+            completed: todo.completed.map(todo::format_time),
+            parent,
+            depth,
+            list: list.to_string(),
+        });
+        collect(&todo.children, list, Some(todo.id), depth + 1, items);
+    }
+}
+
+// `lists` is the number of lists in the file, their headings are printed if there are several
+fn output(items: &[Item], lists: usize, json: bool) -> String {
+    if json {
+        return serde_json::to_string_pretty(items).expect("todos are valid json") + "\n";
+    }
+    let mut out = String::new();
+    let mut heading = None;
+    for item in items {
+        if lists > 1 && heading != Some(&item.list) {
+            heading = Some(&item.list);
+            out += &(item.list.clone() + "\n");
+        }
+        out += &(item.line() + "\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codec::Kind;
+    use crate::storage::FileStorage;
+    use clap::Parser;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: Command,
+    }
+
+    const DOC: &str = "## Todo\n- [x] old <!-- id:1 -->\n- [ ] a <!-- id:2 pri:A tags:x -->\n  - [ ] a.1 <!-- id:3 -->\n- [ ] b <!-- id:4 -->\n";
+
+    fn app(name: &str) -> App {
+        open(name, DOC)
+    }
+
+    fn open(name: &str, doc: &str) -> App {
+        let path =
+            std::env::temp_dir().join(format!("ratatatui-{}-cli-{}.md", std::process::id(), name));
+        std::fs::write(&path, doc).unwrap();
+        let app = load(&path);
+        std::fs::remove_file(&path).unwrap();
+        app
+    }
+
+    fn load(path: &Path) -> App {
+        let mut app = App::new(Box::new(FileStorage::new(
+            PathBuf::from(path),
+            Kind::Markdown.codec("## Todo"),
+        )));
+        app.load().unwrap();
+        app
+    }
+
+    fn run_args(app: &mut App, args: &str) -> io::Result<String> {
+        let cli = Cli::try_parse_from(format!("ratatatui {}", args).split(' ')).unwrap();
+        run(app, cli.command, false)
+    }
+
+    #[test]
+    fn list_todos() {
+        let mut state = app("list");
+        assert_eq!(
+            run_args(&mut state, "list").unwrap(),
+            "   2 [ ] (A) a #x\n   3   [ ] a.1\n   4 [ ] b\n"
+        );
+        assert!(run_args(&mut state, "list --all")
+            .unwrap()
+            .starts_with("   1 [x] old\n"));
+
+        let cli = Cli::try_parse_from(["ratatatui", "list"]).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&run(&mut state, cli.command, true).unwrap()).unwrap();
+        assert_eq!(json[1]["text"], "a.1");
+        assert_eq!(json[1]["parent"], 2);
+        assert_eq!(json[0]["priority"], "A");
+    }
+
+    #[test]
+    fn add_todos() {
+        let mut state = app("add");
+        assert_eq!(
+            run_args(&mut state, "add buy milk -p b -t home").unwrap(),
+            "   5 [ ] (B) buy milk #home\n"
+        );
+        assert_eq!(
+            run_args(&mut state, "add c --under 4").unwrap(),
+            "   6   [ ] c\n"
+        );
+        assert!(run_args(&mut state, "add d --under 9").is_err());
+        assert!(run_args(&mut state, "add d -p 1").is_err());
+        assert!(crate::markdown::write_body(&state.current())
+            .contains("- [ ] b <!-- id:4 -->\n  - [ ] c <!-- id:6 "));
+        assert_eq!(
+            state
+                .file_order()
+                .iter()
+                .map(|t| t.text.as_str())
+                .collect::<Vec<_>>(),
+            ["old", "a", "b", "buy milk"]
+        );
+    }
+
+    #[test]
+    fn done_undone_and_rm() {
+        let mut state = app("done");
+        run_args(&mut state, "done 2 --recursive").unwrap();
+        assert_eq!(run_args(&mut state, "list").unwrap(), "   4 [ ] b\n");
+        run_args(&mut state, "undone 1").unwrap();
+        assert_eq!(
+            run_args(&mut state, "list").unwrap(),
+            "   1 [ ] old\n   4 [ ] b\n"
+        );
+
+        // removing a parent removes its subtasks, naming both is fine
+        assert_eq!(
+            run_args(&mut state, "rm 2 3").unwrap(),
+            "   2 [x] (A) a #x\n   3   [x] a.1\n"
+        );
+        assert!(run_args(&mut state, "rm 2").is_err());
+        assert_eq!(
+            run_args(&mut state, "list --all").unwrap(),
+            "   1 [ ] old\n   4 [ ] b\n"
+        );
+    }
+
+    #[test]
+    fn move_todos() {
+        let mut state = app("move");
+        run_args(&mut state, "move 4 --before 2").unwrap();
+        run_args(&mut state, "move 3 --top").unwrap();
+        assert_eq!(
+            run_args(&mut state, "list").unwrap(),
+            "   4 [ ] b\n   2 [ ] (A) a #x\n   3 [ ] a.1\n"
+        );
+        run_args(&mut state, "move 2 --under 4").unwrap();
+        run_args(&mut state, "move 3 --after 4").unwrap();
+        assert_eq!(
+            run_args(&mut state, "list").unwrap(),
+            "   4 [ ] b\n   2   [ ] (A) a #x\n   3 [ ] a.1\n"
+        );
+        assert!(run_args(&mut state, "move 4 --under 2").is_err());
+        assert!(Cli::try_parse_from(["ratatatui", "move", "4"]).is_err());
+        assert!(Cli::try_parse_from(["ratatatui", "move", "4", "--top", "--under", "2"]).is_err());
+    }
+
+    #[test]
+    fn every_list_of_the_file() {
+        let mut state = open(
+            "lists",
+            "## Backlog\n- [ ] a <!-- id:1 -->\n## Bugs\n- [ ] b <!-- id:2 -->\n",
+        );
+        assert_eq!(
+            run_args(&mut state, "list").unwrap(),
+            "## Backlog\n   1 [ ] a\n## Bugs\n   2 [ ] b\n"
+        );
+        assert_eq!(
+            run_args(&mut state, "done 2").unwrap(),
+            "## Bugs\n   2 [x] b\n"
+        );
+        run_args(&mut state, "move 1 --after 2").unwrap();
+        assert_eq!(
+            run_args(&mut state, "list --all").unwrap(),
+            "## Bugs\n   2 [x] b\n   1 [ ] a\n"
+        );
+
+        // new todos go to the list opened first
+        assert_eq!(
+            run_args(&mut state, "add c").unwrap(),
+            "## Backlog\n   3 [ ] c\n"
+        );
+        run_args(&mut state, "rm 2").unwrap();
+        let lists = state.sections();
+        assert_eq!(lists[0].todos[0].text, "c");
+        assert_eq!(lists[1].todos[0].text, "a");
+        assert_eq!(lists[1].todos.len(), 1);
+    }
+
+    #[test]
+    fn ids_stay_the_same_from_run_to_run() {
+        let path =
+            std::env::temp_dir().join(format!("ratatatui-{}-cli-ids.md", std::process::id()));
+        std::fs::write(&path, "## Todo\n- [ ] a\n- [ ] b\n- [ ] c\n").unwrap();
+        // a new process each time, saving like main does
+        let run_once = |args: &str| {
+            let mut state = load(&path);
+            let output = run_args(&mut state, args).unwrap();
+            if state.is_dirty() {
+                state.save().unwrap();
+            }
+            output
+        };
+        assert_eq!(run_once("list"), "   1 [ ] a\n   2 [ ] b\n   3 [ ] c\n");
+        run_once("rm 1");
+        assert_eq!(run_once("done 2"), "   2 [x] b\n");
+        assert_eq!(run_once("list"), "   3 [ ] c\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn export_then_import() {
+        let path = std::env::temp_dir().join(format!("ratatatui-{}-cli.json", std::process::id()));
+        let mut state = app("export");
+        assert!(run_args(&mut state, "export --format jsonl")
+            .unwrap()
+            .starts_with("{\"schema\":1,\"parent\":null,\"list\":\"## Todo\",\"id\":1,"));
+        run_args(&mut state, &format!("export {}", path.display())).unwrap();
+
+        let mut other = app("import");
+        run_args(&mut other, "rm 1 2 4").unwrap();
+        assert_eq!(
+            run_args(&mut other, &format!("import {}", path.display())).unwrap(),
+            run_args(&mut state, "list --all").unwrap()
+        );
+        run_args(&mut other, &format!("import -a {}", path.display())).unwrap();
+        assert_eq!(other.file_order().len(), 6);
+
+        // every list goes along
+        let doc = "## Todo\n- [ ] a <!-- id:1 -->\n## Bugs\n- [ ] crash <!-- id:2 -->\n";
+        let mut state = open("lists", doc);
+        run_args(&mut state, &format!("export {}", path.display())).unwrap();
+        let mut other = open("lists", doc);
+        run_args(&mut other, "rm 1 2").unwrap();
+        run_args(&mut other, &format!("import {}", path.display())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            run_args(&mut other, "list").unwrap(),
+            "## Todo\n   1 [ ] a\n## Bugs\n   2 [ ] crash\n"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::markdown::{self, Markdown};
+use crate::todo::{self, Todo};
+use crate::todotxt::TodoTxt;
+use std::io;
+use std::path::Path;
+
+// File formats the todo lists can be kept in. A codec reads the lists out of a whole document
+// and writes them back into it, keeping whatever else the document holds.
+
+// A todo list of a document and the lines around it that are not todos
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Section {
+    // tells the lists of a document apart, the heading for markdown
+    pub name: String,
+    // lines that are not todos above the list and below it, the others go with their todo
+    pub passthrough: Vec<String>,
+    pub below: Vec<String>,
+    // in file order
+    pub todos: Vec<Todo>,
+}
+
+impl Section {
+    // Without what only matters while the app runs, to compare what gets written
+    pub fn stored(&self) -> Section {
+        let mut section = self.clone();
+        todo::for_each_mut(&mut section.todos, &mut |todo| {
+            todo.collapsed = false;
+            todo.origin = None;
+        });
+        section
+    }
+}
+
+pub trait Codec {
+    // Every list of the document, the one to open first comes first. Err with NotFound if the
+    // document has no place for todos yet, saving creates it.
+    fn decode(&self, doc: &str) -> io::Result<Vec<Section>>;
+
+    // `doc` with its lists replaced by `sections`, lists it does not have yet are added
+    fn encode(&self, doc: &str, sections: &[Section]) -> String;
+
+    // The lists of a document that has none yet
+    fn empty(&self) -> Vec<Section>;
+}
+
+// Lists as compared to tell if there are unsaved changes
+pub fn stored(sections: &[Section]) -> Vec<Section> {
+    sections.iter().map(Section::stored).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Kind {
+    Markdown,
+    TodoTxt,
+}
+
+impl Kind {
+    // todo.txt for `.txt` files, markdown otherwise
+    pub fn of(path: &Path) -> Kind {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("txt") => Kind::TodoTxt,
+            _ => Kind::Markdown,
+        }
+    }
+
+    // `section` is the markdown heading of the todo list
+    pub fn codec(self, section: &str) -> Box<dyn Codec> {
+        match self {
+            Kind::Markdown => Box::new(Markdown {
+                heading: markdown::heading(section),
+            }),
+            Kind::TodoTxt => Box::new(TodoTxt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kind_from_extension() {
+        assert_eq!(Kind::of(Path::new("todo.txt")), Kind::TodoTxt);
+        assert_eq!(Kind::of(Path::new("README.md")), Kind::Markdown);
+        assert_eq!(Kind::of(Path::new("TODO")), Kind::Markdown);
+    }
+}
@@ -0,0 +1,14 @@
+use std::env;
+use std::path::PathBuf;
+
+// The files in the config directory: `keys.toml` for the keymap and `theme.toml` for the
+// colors. All of them are optional.
+
+// `name` in `$XDG_CONFIG_HOME/ratatatui`, or in `~/.config/ratatatui` without it
+pub fn path(name: &str) -> Option<PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("ratatatui").join(name))
+}
@@ -0,0 +1,194 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Single line text editor used by INSERT and EDIT mode.
+// The cursor is a byte offset that always sits on a grapheme boundary, so combined
+// characters and emoji are moved over and deleted as a whole.
+
+#[derive(Debug, Default, PartialEq)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    // Start editing `text` with the cursor at its end
+    pub fn new(text: &str) -> Self {
+        LineEditor {
+            text: text.to_string(),
+            cursor: text.len(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Byte offset of the cursor in the text
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Apply a key press, returns false for keys the editor does not use
+    pub fn handle(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('w') if ctrl => self.delete_word(),
+            KeyCode::Char(_) if ctrl || key.modifiers.contains(KeyModifiers::ALT) => return false,
+            KeyCode::Char(c) => self.insert(c),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left if ctrl => self.cursor = self.word_left(),
+            KeyCode::Right if ctrl => self.cursor = self.word_right(),
+            KeyCode::Left => self.cursor = self.prev_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        // a combining character may have merged with the grapheme before it
+        self.cursor = self.next_boundary_from(self.prev_boundary());
+    }
+
+    pub fn backspace(&mut self) {
+        let start = self.prev_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    pub fn delete(&mut self) {
+        let end = self.next_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    // Ctrl-W: delete back to the start of the word, like a shell
+    pub fn delete_word(&mut self) {
+        let start = self.word_left();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    fn prev_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.next_boundary_from(self.cursor)
+    }
+
+    fn next_boundary_from(&self, from: usize) -> usize {
+        self.text[from..]
+            .graphemes(true)
+            .next()
+            .map_or(self.text.len(), |g| from + g.len())
+    }
+
+    // Start of the word before the cursor, skipping the whitespace in between
+    fn word_left(&self) -> usize {
+        let mut start = self.cursor;
+        let mut in_word = false;
+        for (index, g) in self.text[..self.cursor].grapheme_indices(true).rev() {
+            let space = g.trim().is_empty();
+            if in_word && space {
+                break;
+            }
+            in_word |= !space;
+            start = index;
+        }
+        start
+    }
+
+    // End of the word after the cursor, skipping the whitespace in between
+    fn word_right(&self) -> usize {
+        let mut end = self.cursor;
+        let mut in_word = false;
+        for g in self.text[self.cursor..].graphemes(true) {
+            let space = g.trim().is_empty();
+            if in_word && space {
+                break;
+            }
+            in_word |= !space;
+            end += g.len();
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) {
+        editor.handle(KeyEvent::new(code, modifiers));
+    }
+
+    fn typed(text: &str) -> LineEditor {
+        let mut editor = LineEditor::default();
+        text.chars().for_each(|c| editor.insert(c));
+        editor
+    }
+
+    #[test]
+    fn insert_in_the_middle() {
+        let mut editor = typed("helo");
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('l'), KeyModifiers::NONE);
+        assert_eq!(editor.text(), "hello");
+        assert_eq!(editor.cursor(), 4);
+
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Delete, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('H'), KeyModifiers::SHIFT);
+        assert_eq!(editor.text(), "Hello");
+        press(&mut editor, KeyCode::End, KeyModifiers::NONE);
+        assert_eq!(editor.cursor(), 5);
+    }
+
+    #[test]
+    fn graphemes_are_edited_whole() {
+        // "e" followed by a combining acute accent, and a family emoji
+        let mut editor = typed("cafe\u{301} 👨‍👩‍👧");
+        assert_eq!(editor.cursor(), "cafe\u{301} 👨‍👩‍👧".len());
+        editor.backspace();
+        assert_eq!(editor.text(), "cafe\u{301} ");
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        editor.delete();
+        assert_eq!(editor.text(), "caf ");
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut editor = typed("fix  the parser");
+        press(&mut editor, KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(editor.cursor(), 9);
+        press(&mut editor, KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(editor.cursor(), 5);
+        press(&mut editor, KeyCode::Right, KeyModifiers::CONTROL);
+        assert_eq!(editor.cursor(), 8);
+
+        press(&mut editor, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert_eq!(editor.text(), "fix   parser");
+        press(&mut editor, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert_eq!(editor.text(), " parser");
+        press(&mut editor, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert_eq!(editor.text(), " parser");
+    }
+
+    #[test]
+    fn unused_keys_are_reported() {
+        let mut editor = typed("a");
+        assert!(!editor.handle(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        assert!(!editor.handle(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL)));
+        assert_eq!(editor.text(), "a");
+    }
+}
@@ -0,0 +1,340 @@
+use crate::codec::Section;
+use crate::todo::{self, Todo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+// Structured copies of the todo lists for dashboards and scripts. JSON is one document holding
+// the tree of todos, JSON Lines is one todo per line in file order with the id of its parent.
+// Top level todos name the list they are in. Both carry the schema version so readers can tell
+// when the layout changes.
+
+// Bump when fields change meaning or are removed, adding optional fields is fine
+pub const SCHEMA: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Jsonl,
+}
+
+impl Format {
+    // JSON Lines for `.jsonl` and `.ndjson` files, JSON otherwise
+    pub fn of(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl") | Some("ndjson") => Format::Jsonl,
+            _ => Format::Json,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Document {
+    schema: u32,
+    todos: Vec<Record>,
+}
+
+// A todo as it is exported, ids of 0 or missing ones are handed out on import
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Record {
+    // the list of a top level todo, subtasks are in the list of their parent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    list: Option<String>,
+    #[serde(default)]
+    id: u64,
+    text: String,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    priority: Option<char>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    created: Option<String>,
+    #[serde(default)]
+    completed: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<Record>,
+}
+
+// A line of JSON Lines, subtasks follow their parent
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Line {
+    schema: u32,
+    #[serde(default)]
+    parent: Option<u64>,
+    #[serde(flatten)]
+    todo: Record,
+}
+
+// The todos of every list, in file order
+pub fn export(lists: &[Section], format: Format) -> String {
+    let records = lists.iter().flat_map(|list| {
+        list.todos.iter().map(|todo| Record {
+            list: Some(list.name.clone()),
+            ..record(todo)
+        })
+    });
+    match format {
+        Format::Json => {
+            let document = Document {
+                schema: SCHEMA,
+                todos: records.collect(),
+            };
+            serde_json::to_string_pretty(&document).expect("todos are valid json") + "\n"
+        }
+        Format::Jsonl => {
+            let mut out = String::new();
+            for record in records {
+                write_lines(record, None, &mut out);
+            }
+            out
+        }
+    }
+}
+
+fn record(todo: &Todo) -> Record {
+    Record {
+        list: None,
+        id: todo.id,
+        text: todo.text.clone(),
+        done: todo.done,
+        priority: todo.priority,
+        tags: todo.tags.clone(),
+        created: todo.created.map(todo::format_time),
+        completed: todo.completed.map(todo::format_time),
+        children: todo.children.iter().map(record).collect(),
+    }
+}
+
+// A record and its subtasks, one line each
+fn write_lines(mut record: Record, parent: Option<u64>, out: &mut String) {
+    let children = std::mem::take(&mut record.children);
+    let id = record.id;
+    let line = Line {
+        schema: SCHEMA,
+        parent,
+        todo: record,
+    };
+    *out += &(serde_json::to_string(&line).expect("todos are valid json") + "\n");
+    for child in children {
+        write_lines(child, Some(id), out);
+    }
+}
+
+// Todos in file order out of an export, grouped by the list they name. Todos without one are
+// in a list without a name.
+pub fn import(text: &str, format: Format) -> io::Result<Vec<Section>> {
+    let (names, todos) = match format {
+        Format::Json => {
+            let document: Document = serde_json::from_str(text).map_err(invalid)?;
+            check_schema(document.schema)?;
+            let names = document.todos.iter().map(|r| r.list.clone()).collect();
+            let todos = document
+                .todos
+                .into_iter()
+                .map(parse_record)
+                .collect::<io::Result<Vec<_>>>()?;
+            (names, todos)
+        }
+        Format::Jsonl => import_lines(text)?,
+    };
+
+    let mut seen = HashSet::new();
+    let mut duplicate = None;
+    todo::for_each(&todos, &mut |todo| {
+        if todo.id != 0 && !seen.insert(todo.id) {
+            duplicate = Some(todo.id);
+        }
+    });
+    if let Some(id) = duplicate {
+        return Err(invalid(format!("todo id {} is used twice", id)));
+    }
+
+    let mut lists: Vec<Section> = Vec::new();
+    for (name, todo) in names.into_iter().zip(todos) {
+        let name = name.unwrap_or_default();
+        let index = match lists.iter().position(|list| list.name == name) {
+            Some(index) => index,
+            None => {
+                lists.push(Section {
+                    name,
+                    ..Default::default()
+                });
+                lists.len() - 1
+            }
+        };
+        lists[index].todos.push(todo);
+    }
+    Ok(lists)
+}
+
+// The list each top level todo names and the todos
+fn import_lines(text: &str) -> io::Result<(Vec<Option<String>>, Vec<Todo>)> {
+    let mut names = Vec::new();
+    let mut todos = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at_line = |err: String| invalid(format!("line {}: {}", number + 1, err));
+        let line: Line = serde_json::from_str(line).map_err(|err| at_line(err.to_string()))?;
+        check_schema(line.schema).map_err(|err| at_line(err.to_string()))?;
+        let name = line.todo.list.clone();
+        let todo = parse_record(line.todo).map_err(|err| at_line(err.to_string()))?;
+
+        let siblings = match line.parent {
+            None => {
+                names.push(name);
+                &mut todos
+            }
+            Some(parent) => {
+                let path = todo::find_id(&todos, parent)
+                    .ok_or_else(|| at_line(format!("parent {} is not above this line", parent)))?;
+                &mut todo::get_mut(&mut todos, &path).unwrap().children
+            }
+        };
+        siblings.push(todo);
+    }
+    Ok((names, todos))
+}
+
+fn parse_record(record: Record) -> io::Result<Todo> {
+    let time = |value: Option<String>| match value {
+        None => Ok(None),
+        Some(value) => todo::parse_time(&value)
+            .map(Some)
+            .ok_or_else(|| invalid(format!("\"{}\" is not an RFC 3339 time", value))),
+    };
+    let priority = match record.priority {
+        Some(p) if !p.is_ascii_uppercase() => {
+            return Err(invalid(format!(
+                "priority {} is not a letter from A to Z",
+                p
+            )))
+        }
+        p => p,
+    };
+    Ok(Todo {
+        id: record.id,
+        // written back like the ids read from the file
+        id_in_file: record.id != 0,
+        text: record.text,
+        done: record.done,
+        priority,
+        tags: record.tags,
+        created: time(record.created)?,
+        completed: time(record.completed)?,
+        children: record
+            .children
+            .into_iter()
+            .map(parse_record)
+            .collect::<io::Result<_>>()?,
+        ..Default::default()
+    })
+}
+
+fn check_schema(schema: u32) -> io::Result<()> {
+    if schema == SCHEMA {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "schema version {} is not supported, expected {}",
+            schema, SCHEMA
+        )))
+    }
+}
+
+fn invalid(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn todos() -> Vec<Todo> {
+        let mut done = Todo::parse("ship it <!-- id:1 created:2024-05-01T10:00:00Z -->", true);
+        done.completed = todo::parse_time("2024-05-02T10:00:00Z");
+        let mut parent = Todo::parse("write docs <!-- id:2 pri:A tags:docs,web -->", false);
+        parent.children = vec![
+            Todo::parse("intro <!-- id:3 -->", true),
+            Todo::parse("usage <!-- id:4 -->", false),
+        ];
+        vec![done, parent]
+    }
+
+    fn lists() -> Vec<Section> {
+        let list = |name: &str, todos| Section {
+            name: name.to_string(),
+            todos,
+            ..Default::default()
+        };
+        vec![
+            list("## Todo", todos()),
+            list("## Bugs", vec![Todo::parse("crash <!-- id:5 -->", false)]),
+        ]
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let text = export(&lists(), Format::Json);
+        assert!(text.starts_with("{\n  \"schema\": 1,"));
+        assert_eq!(import(&text, Format::Json).unwrap(), lists());
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let text = export(&lists(), Format::Jsonl);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with(r###"{"schema":1,"parent":null,"list":"## Todo","id":2,"###));
+        assert_eq!(
+            lines[2],
+            r#"{"schema":1,"parent":2,"id":3,"text":"intro","done":true,"priority":null,"tags":[],"created":null,"completed":null}"#
+        );
+        assert!(lines[4].contains(r###""list":"## Bugs""###));
+        assert_eq!(import(&text, Format::Jsonl).unwrap(), lists());
+    }
+
+    #[test]
+    fn minimal_records() {
+        let lists = import(r#"{"schema":1,"todos":[{"text":"a"}]}"#, Format::Json).unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "");
+        assert_eq!(lists[0].todos, vec![Todo::new(String::from("a"), false)]);
+    }
+
+    #[test]
+    fn bad_imports() {
+        let err = |text: &str, format| import(text, format).unwrap_err().to_string();
+        assert_eq!(
+            err(r#"{"schema":2,"todos":[]}"#, Format::Json),
+            "schema version 2 is not supported, expected 1"
+        );
+        assert_eq!(
+            err("{\"schema\":1,\"id\":1,\"text\":\"a\"}\n{\"schema\":1,\"parent\":5,\"text\":\"b\"}", Format::Jsonl),
+            "line 2: parent 5 is not above this line"
+        );
+        assert_eq!(
+            err(
+                r#"{"schema":1,"todos":[{"id":1,"text":"a"},{"id":1,"text":"b"}]}"#,
+                Format::Json
+            ),
+            "todo id 1 is used twice"
+        );
+        assert!(err(
+            r#"{"schema":1,"todos":[{"text":"a","created":"yesterday"}]}"#,
+            Format::Json
+        )
+        .contains("RFC 3339"));
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(Format::of(Path::new("todos.jsonl")), Format::Jsonl);
+        assert_eq!(Format::of(Path::new("todos.json")), Format::Json);
+        assert_eq!(Format::of(Path::new("todos")), Format::Json);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Writing the todo file without ever leaving it half written. The new contents go to a
+// temporary file next to it which then replaces it in one rename, so a crash or a full disk
+// leaves either the old or the new file behind.
+
+// Replace the contents of `path`, keeping its permissions
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let temp = sibling(path, ".tmp");
+    let result = write_temp(path, &temp, contents).and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_temp(path: &Path, temp: &Path, contents: &str) -> io::Result<()> {
+    let mut file = fs::File::create(temp)?;
+    file.write_all(contents.as_bytes())?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+// Copy `path` to `path.bak` before it is replaced, older copies move on to `path.bak.1`,
+// `path.bak.2`, .. and only `keep` copies are kept. Nothing to back up is not an error.
+pub fn backup(path: &Path, keep: usize) -> io::Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..keep).rev() {
+        let older = backup_path(path, n - 1);
+        if older.exists() {
+            fs::rename(older, backup_path(path, n))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 0))?;
+    Ok(())
+}
+
+// `n`th newest backup of `path`
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    match n {
+        0 => sibling(path, ".bak"),
+        n => sibling(path, &format!(".bak.{}", n)),
+    }
+}
+
+// `path` with `suffix` appended to the file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ratatatui-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_replaces_contents() {
+        let dir = temp_dir("write");
+        let path = dir.join("readme.md");
+        write_atomic(&path, "first\n").unwrap();
+        write_atomic(&path, "second\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+        // no temporary file is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_keeps_nothing() {
+        let dir = temp_dir("missing");
+        let path = dir.join("no such dir").join("readme.md");
+        assert!(write_atomic(&path, "text").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_rotate() {
+        let dir = temp_dir("backup");
+        let path = dir.join("readme.md");
+        backup(&path, 2).unwrap();
+        assert!(!backup_path(&path, 0).exists());
+
+        for text in ["1", "2", "3", "4"] {
+            backup(&path, 2).unwrap();
+            write_atomic(&path, text).unwrap();
+        }
+        assert_eq!(fs::read_to_string(backup_path(&path, 0)).unwrap(), "3");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "2");
+        assert!(!backup_path(&path, 2).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use crate::codec::Section;
+use crate::todo::Todo;
+
+// Undo / redo stacks of whole copies of the lists, the lists are small enough that diffing
+// them is not worth the trouble. Snapshots hold every list of the file, so undoing a change
+// also brings back the list it was made in.
+
+// Oldest entries are dropped once the undo stack grows past this
+const LIMIT: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub active: Vec<Todo>,
+    pub completed: Vec<Todo>,
+    pub active_selected: usize,
+    pub completed_selected: usize,
+    // the other lists and which one is on screen
+    pub lists: Vec<Section>,
+    pub tab: usize,
+}
+
+impl Snapshot {
+    fn same_todos(&self, other: &Snapshot) -> bool {
+        self.active == other.active
+            && self.completed == other.completed
+            && self.lists == other.lists
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    // (what the change was, state before it)
+    undo: Vec<(&'static str, Snapshot)>,
+    redo: Vec<(&'static str, Snapshot)>,
+    // the redo stack from before the last record, until settle knows whether anything changed
+    parked: Vec<(&'static str, Snapshot)>,
+}
+
+impl History {
+    // Remember the state before a change, a new change makes the redo stack meaningless
+    pub fn record(&mut self, label: &'static str, before: Snapshot) {
+        self.undo.push((label, before));
+        if self.undo.len() > LIMIT {
+            self.undo.remove(0);
+        }
+        self.parked = std::mem::take(&mut self.redo);
+    }
+
+    // Forget the last record if it turned out nothing changed, e.g. leaving EDIT mode
+    // without typing anything, what could be redone before it can still be
+    pub fn settle(&mut self, current: &Snapshot) {
+        if self
+            .undo
+            .last()
+            .is_some_and(|(_, before)| before.same_todos(current))
+        {
+            self.undo.pop();
+            self.redo = std::mem::take(&mut self.parked);
+        } else {
+            self.parked.clear();
+        }
+    }
+
+    // State to go back to, `current` is kept for redo
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let (label, before) = self.undo.pop()?;
+        self.redo.push((label, current));
+        Some(before)
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let (label, after) = self.redo.pop()?;
+        self.undo.push((label, current));
+        Some(after)
+    }
+
+    // Short description of both stacks for the status bar
+    pub fn get(&self) -> String {
+        let top = |stack: &[(&'static str, Snapshot)]| match stack.last() {
+            Some((label, _)) => format!("{} ({})", label, stack.len()),
+            None => String::from("-"),
+        };
+        format!("UNDO: {}  REDO: {}", top(&self.undo), top(&self.redo))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(texts: &[&str]) -> Snapshot {
+        Snapshot {
+            active: texts
+                .iter()
+                .map(|text| Todo::new(text.to_string(), false))
+                .collect(),
+            completed: Vec::new(),
+            active_selected: 0,
+            completed_selected: 0,
+            lists: Vec::new(),
+            tab: 0,
+        }
+    }
+
+    #[test]
+    fn undo_then_redo() {
+        let mut history = History::default();
+        history.record("insert", snapshot(&[]));
+        history.record("edit", snapshot(&["a"]));
+
+        assert_eq!(history.undo(snapshot(&["b"])), Some(snapshot(&["a"])));
+        assert_eq!(history.undo(snapshot(&["a"])), Some(snapshot(&[])));
+        assert_eq!(history.undo(snapshot(&[])), None);
+        assert_eq!(history.get(), "UNDO: -  REDO: insert (2)");
+
+        assert_eq!(history.redo(snapshot(&[])), Some(snapshot(&["a"])));
+        assert_eq!(history.get(), "UNDO: insert (1)  REDO: edit (1)");
+
+        // a new change drops what could be redone
+        history.record("delete", snapshot(&["a"]));
+        assert_eq!(history.redo(snapshot(&[])), None);
+    }
+
+    #[test]
+    fn settle_drops_empty_changes() {
+        let mut history = History::default();
+        history.record("edit", snapshot(&["a"]));
+        history.settle(&snapshot(&["a"]));
+        assert_eq!(history.undo(snapshot(&["a"])), None);
+
+        history.record("edit", snapshot(&["a"]));
+        history.settle(&snapshot(&["ab"]));
+        assert_eq!(history.undo(snapshot(&["ab"])), Some(snapshot(&["a"])));
+    }
+
+    #[test]
+    fn empty_changes_keep_redo() {
+        let mut history = History::default();
+        history.record("edit", snapshot(&["a"]));
+        assert_eq!(history.undo(snapshot(&["ab"])), Some(snapshot(&["a"])));
+
+        history.record("edit", snapshot(&["a"]));
+        history.settle(&snapshot(&["a"]));
+        assert_eq!(history.redo(snapshot(&["a"])), Some(snapshot(&["ab"])));
+
+        // a real change still drops it
+        history.undo(snapshot(&["ab"]));
+        history.record("edit", snapshot(&["a"]));
+        history.settle(&snapshot(&["ac"]));
+        assert_eq!(history.redo(snapshot(&["ac"])), None);
+    }
+
+    #[test]
+    fn stack_is_limited() {
+        let mut history = History::default();
+        for _ in 0..LIMIT + 10 {
+            history.record("edit", snapshot(&[]));
+        }
+        assert_eq!(history.undo.len(), LIMIT);
+    }
+}
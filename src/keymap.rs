@@ -0,0 +1,467 @@
+use crate::action::Action;
+use crate::state::{Mode, Screen};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::Path;
+use std::{fmt, fs, io};
+
+// Which key does what. Keys are bound per mode, optionally only on one screen:
+//
+//     [read]
+//     down = ["j", "Down"]
+//     [read.completed]
+//     complete = "r"
+//     [grab]
+//     drop = ["Esc", "Enter"]
+//
+// The keys of `keys.toml` in the config directory (see `config::path`) replace the built-in
+// keys of the actions it names, the other actions keep theirs. Only READ, GRAB and VISUAL mode
+// have bindings, the other modes take text or answers.
+
+const DEFAULT: &str = r#"
+[read]
+down = "j"
+up = "k"
+bottom = "G"
+page_down = "PageDown"
+page_up = "PageUp"
+grab = "g"
+switch_screen = "Tab"
+next_list = "]"
+previous_list = "["
+edit = "e"
+complete = "m"
+delete = "d"
+fold = "Space"
+priority = "p"
+indent = ">"
+outdent = "<"
+move_to_next_list = "}"
+move_to_previous_list = "{"
+undo = "u"
+redo = "C-r"
+search = "/"
+next_match = "n"
+previous_match = "N"
+clear_search = "Esc"
+command = ":"
+quit = "q"
+visual = "v"
+
+[read.active]
+insert = "i"
+
+[grab]
+move_down = "j"
+move_up = "k"
+drop = ["Esc", "Enter"]
+
+[visual]
+down = "j"
+up = "k"
+bottom = "G"
+page_down = "PageDown"
+page_up = "PageUp"
+toggle = "Space"
+complete = "m"
+delete = "d"
+move_down = "J"
+move_up = "K"
+move_to_next_list = "}"
+move_to_previous_list = "{"
+visual = ["v", "Esc"]
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    pub code: KeyCode,
+    // only Ctrl and Alt, shift is part of the character
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn matches(&self, key: KeyEvent) -> bool {
+        let modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.code == key.code && self.modifiers == modifiers
+    }
+}
+
+// Characters stand for themselves, `C-r` / `A-r` add Ctrl / Alt, and the other keys go by
+// name: `Tab`, `Space`, `Enter`, `PageDown`, ..
+impl std::str::FromStr for Key {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Key, String> {
+        let unknown = || format!("unknown key \"{}\"", text);
+        let (modifiers, name) = match text.split_once('-') {
+            Some(("C" | "Ctrl", name)) if !name.is_empty() => (KeyModifiers::CONTROL, name),
+            Some(("A" | "Alt", name)) if !name.is_empty() => (KeyModifiers::ALT, name),
+            _ => (KeyModifiers::NONE, text),
+        };
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name.to_lowercase().as_str() {
+                "tab" => KeyCode::Tab,
+                "space" => KeyCode::Char(' '),
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                _ => return Err(unknown()),
+            },
+        };
+        Ok(Key { code, modifiers })
+    }
+}
+
+// As shown in the KEY BINDING panel
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "SPACE"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Tab => write!(f, "TAB"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Binding {
+    mode: Mode,
+    // None for both screens
+    screen: Option<Screen>,
+    key: Key,
+    action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        keymap.apply(DEFAULT).expect("built-in keys are valid");
+        keymap
+    }
+}
+
+impl Keymap {
+    // The built-in keys changed by the config file at `path`
+    pub fn load(path: &Path) -> io::Result<Keymap> {
+        let mut keymap = Keymap::default();
+        let text = fs::read_to_string(path)?;
+        keymap
+            .apply(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(keymap)
+    }
+
+    pub fn action(&self, mode: &Mode, screen: &Screen, key: KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|b| {
+                b.mode == *mode && b.screen.unwrap_or(*screen) == *screen && b.key.matches(key)
+            })
+            .map(|b| b.action)
+    }
+
+    // Keys of `action` on `screen`, the ones bound only there first
+    pub fn keys(&self, mode: &Mode, screen: &Screen, action: Action) -> Vec<Key> {
+        let mut bindings: Vec<&Binding> = self
+            .bindings
+            .iter()
+            .filter(|b| b.mode == *mode && b.screen.unwrap_or(*screen) == *screen)
+            .filter(|b| b.action == action)
+            .collect();
+        bindings.sort_by_key(|b| b.screen.is_none());
+        bindings.into_iter().map(|b| b.key).collect()
+    }
+
+    fn apply(&mut self, text: &str) -> Result<(), String> {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|err: toml::de::Error| err.message().to_string())?;
+        let mut bindings = Vec::new();
+        for (name, value) in &table {
+            let mode = match name.as_str() {
+                "read" => Mode::READ,
+                "grab" => Mode::GRAB,
+                "visual" => Mode::VISUAL,
+                _ => {
+                    return Err(format!(
+                        "[{}]: only [read], [grab] and [visual] have keys",
+                        name
+                    ))
+                }
+            };
+            let table = value
+                .as_table()
+                .ok_or_else(|| format!("{} is not a table", name))?;
+            parse_table(mode, None, name, table, &mut bindings)?;
+        }
+
+        // new keys replace the built-in ones of the same action on the same screens
+        self.bindings.retain(|old| {
+            !bindings.iter().any(|new| {
+                new.mode == old.mode && new.screen == old.screen && new.action == old.action
+            })
+        });
+        self.bindings.append(&mut bindings);
+        self.check()
+    }
+
+    // A key may do one thing per mode and screen
+    fn check(&self) -> Result<(), String> {
+        for (index, a) in self.bindings.iter().enumerate() {
+            for b in &self.bindings[index + 1..] {
+                let overlap = match (a.screen, b.screen) {
+                    (Some(x), Some(y)) => x == y,
+                    _ => true,
+                };
+                if a.mode == b.mode && overlap && a.key == b.key && a.action != b.action {
+                    return Err(format!(
+                        "{} is bound to both {} and {} in {}",
+                        a.key,
+                        a.action.name(),
+                        b.action.name(),
+                        a.mode.get()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Text of the KEY BINDING panel on `screen`
+    pub fn help(&self, mode: &Mode, screen: &Screen) -> String {
+        // GRAB mode only has a few keys, the READ mode ones are more useful to see
+        let mode = if *mode == Mode::VISUAL {
+            Mode::VISUAL
+        } else {
+            Mode::READ
+        };
+        let keys = |action| {
+            self.keys(&mode, screen, action)
+                .first()
+                .map(|key| key.to_string())
+        };
+        let reopen = if *screen == Screen::ACTIVE {
+            "to Complete"
+        } else {
+            "to Reopen"
+        };
+        let visual: [(&[Action], &str); 7] = [
+            (&[Action::Visual], "to leave"),
+            (&[Action::Toggle], "to select one"),
+            (&[Action::Bottom], "for Bottom"),
+            (&[Action::Complete], reopen),
+            (&[Action::Delete], "to Delete"),
+            (&[Action::MoveUp, Action::MoveDown], "to Move"),
+            (
+                &[Action::MoveToPreviousList, Action::MoveToNextList],
+                "to move to a List",
+            ),
+        ];
+        let read: [(&[Action], &str); 20] = [
+            (&[Action::SwitchScreen], "to switch Tabs"),
+            (&[Action::Insert], "to Insert"),
+            (&[Action::Edit], "to Edit"),
+            (&[Action::Grab], "to enter Grab mode"),
+            (&[Action::Grab, Action::Bottom], "for Top / Bottom"),
+            (&[Action::PageUp, Action::PageDown], "to Scroll"),
+            (&[Action::Complete], reopen),
+            (&[Action::Delete], "to Delete"),
+            (&[Action::Undo, Action::Redo], "to Undo / Redo"),
+            (&[Action::Priority], "for Priority"),
+            (&[Action::Fold], "to Fold"),
+            (&[Action::Indent, Action::Outdent], "to Indent"),
+            (&[Action::Visual], "to select several"),
+            (&[Action::PreviousList, Action::NextList], "to switch Lists"),
+            (
+                &[Action::MoveToPreviousList, Action::MoveToNextList],
+                "to move to a List",
+            ),
+            (&[Action::Search], "to Search"),
+            (&[Action::ClearSearch], "to clear the Search"),
+            (
+                &[Action::NextMatch, Action::PreviousMatch],
+                "for next / previous Match",
+            ),
+            (
+                &[Action::Command],
+                "for :w :q :wq :q!\n  :export / :import FILE",
+            ),
+            (&[Action::Quit], "to Exit"),
+        ];
+
+        let lines: &[(&[Action], &str)] = if mode == Mode::VISUAL { &visual } else { &read };
+        let mut help = Vec::new();
+        for (actions, text) in lines {
+            let Some(mut keys) = actions.iter().map(|a| keys(*a)).collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            // the grab key pressed twice jumps to the top
+            if *actions == [Action::Grab, Action::Bottom] {
+                keys[0] = keys[0].repeat(2);
+            }
+            help.push(format!("Click {} {}", keys.join(" / "), text));
+        }
+        help.join("\n")
+    }
+}
+
+fn parse_table(
+    mode: Mode,
+    screen: Option<Screen>,
+    section: &str,
+    table: &toml::Table,
+    bindings: &mut Vec<Binding>,
+) -> Result<(), String> {
+    for (name, value) in table {
+        let screen_table = match name.as_str() {
+            "active" => Some(Screen::ACTIVE),
+            "completed" => Some(Screen::COMPLETED),
+            _ => None,
+        };
+        if let (Some(inner), None, Some(table)) = (screen_table, screen, value.as_table()) {
+            parse_table(
+                mode,
+                Some(inner),
+                &format!("{}.{}", section, name),
+                table,
+                bindings,
+            )?;
+            continue;
+        }
+
+        let at = |err: String| format!("[{}] {}: {}", section, name, err);
+        let action = Action::from_name(name)
+            .filter(|action| action.works_in(mode))
+            .ok_or_else(|| at(String::from("no such action")))?;
+        let keys: Vec<&toml::Value> = match value {
+            toml::Value::Array(keys) => keys.iter().collect(),
+            key => vec![key],
+        };
+        for key in keys {
+            let key = key
+                .as_str()
+                .ok_or_else(|| at(String::from("keys are strings")))?;
+            bindings.push(Binding {
+                mode,
+                screen,
+                key: key.parse().map_err(at)?,
+                action,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::keys;
+
+    fn action(keymap: &Keymap, mode: Mode, screen: Screen, text: &str) -> Option<Action> {
+        keymap.action(&mode, &screen, keys(text)[0])
+    }
+
+    #[test]
+    fn default_bindings() {
+        let keymap = Keymap::default();
+        let read = |screen, text| action(&keymap, Mode::READ, screen, text);
+        assert_eq!(read(Screen::ACTIVE, "j"), Some(Action::Down));
+        assert_eq!(read(Screen::ACTIVE, "i"), Some(Action::Insert));
+        assert_eq!(read(Screen::COMPLETED, "i"), None);
+        assert_eq!(read(Screen::ACTIVE, "r"), None);
+        assert_eq!(read(Screen::ACTIVE, "<C-r>"), Some(Action::Redo));
+        assert_eq!(
+            action(&keymap, Mode::GRAB, Screen::ACTIVE, "j"),
+            Some(Action::MoveDown)
+        );
+        assert_eq!(action(&keymap, Mode::INSERT, Screen::ACTIVE, "j"), None);
+    }
+
+    #[test]
+    fn config_replaces_keys() {
+        let mut keymap = Keymap::default();
+        keymap
+            .apply("[read]\ndown = [\"J\", \"Down\"]\n[read.completed]\ncomplete = \"r\"\n")
+            .unwrap();
+        let read = |screen, text| action(&keymap, Mode::READ, screen, text);
+        assert_eq!(read(Screen::ACTIVE, "J"), Some(Action::Down));
+        assert_eq!(read(Screen::ACTIVE, "j"), None);
+        assert_eq!(read(Screen::COMPLETED, "r"), Some(Action::Complete));
+        // keys for one screen leave the other screen alone
+        assert_eq!(read(Screen::ACTIVE, "m"), Some(Action::Complete));
+        assert_eq!(read(Screen::ACTIVE, "r"), None);
+        assert!(keymap
+            .help(&Mode::READ, &Screen::COMPLETED)
+            .contains("Click r to Reopen"));
+        assert!(keymap
+            .help(&Mode::READ, &Screen::ACTIVE)
+            .contains("Click m to Complete"));
+    }
+
+    #[test]
+    fn bad_configs() {
+        let err = |text| Keymap::default().apply(text).unwrap_err();
+        assert_eq!(
+            err("[read]\ncomplete = \"j\""),
+            "j is bound to both down and complete in READ MODE"
+        );
+        assert_eq!(err("[read]\njump = \"j\""), "[read] jump: no such action");
+        assert_eq!(err("[grab]\nquit = \"q\""), "[grab] quit: no such action");
+        assert_eq!(
+            err("[read]\nquit = \"F13\""),
+            "[read] quit: unknown key \"F13\""
+        );
+        assert_eq!(
+            err("[edit]\n"),
+            "[edit]: only [read], [grab] and [visual] have keys"
+        );
+        // the same key may do different things on each screen
+        Keymap::default()
+            .apply("[read.active]\nquit = \"x\"\n[read.completed]\nedit = \"x\"\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn key_names() {
+        let key = |text: &str| text.parse::<Key>().unwrap();
+        assert_eq!(key("C-r").to_string(), "Ctrl-r");
+        assert_eq!(key("Space").to_string(), "SPACE");
+        assert_eq!(key("pagedown").code, KeyCode::PageDown);
+        assert_eq!(key("-").code, KeyCode::Char('-'));
+        assert!(key("G").matches(KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT)));
+    }
+
+    #[test]
+    fn help_matches_old_text() {
+        assert!(Keymap::default().help(&Mode::READ, &Screen::ACTIVE).starts_with(
+            "Click TAB to switch Tabs\nClick i to Insert\nClick e to Edit\nClick g to enter Grab mode\nClick gg / G for Top / Bottom\nClick PgUp / PgDn to Scroll\nClick m to Complete"
+        ));
+    }
+}
@@ -1,28 +1,168 @@
+mod action;
 mod app;
+mod cli;
+mod codec;
+mod config;
+mod editor;
+mod export;
+mod file;
+mod history;
+mod keymap;
+mod markdown;
+mod merge;
+mod parser;
+mod search;
 mod state;
+mod storage;
+mod terminal;
+mod theme;
+mod todo;
+mod todotxt;
 mod ui;
+mod wrap;
 
 use app::App;
-use crossterm::{
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
-};
+use clap::Parser;
+use cli::Command;
+use codec::Kind;
+use keymap::Keymap;
 use ratatui::prelude::*;
-use std::io::{stdout, Result};
+use state::Autosave;
+use std::env;
+use std::io::{stdout, ErrorKind, Result};
+use std::path::PathBuf;
+use std::process;
+use storage::FileStorage;
+use theme::{Base, Theme};
 use ui::ui;
 
+/// Keep a todo list inside a section of a markdown file
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Markdown file the todo list is read from and saved to
+    #[arg(short, long, default_value = "readme.md")]
+    file: PathBuf,
+
+    /// Heading of the todo list to open first, e.g. "## Todo" or "Backlog". Every other
+    /// section with a task list is opened too, as a tab
+    #[arg(short, long, default_value = "## Todo")]
+    section: String,
+
+    /// Create the file and the section on save if they do not exist yet
+    #[arg(short, long)]
+    create: bool,
+
+    /// Keep this many backup copies of the file (file.bak, file.bak.1, ..) when saving
+    #[arg(short, long, default_value_t = 0, value_name = "COUNT")]
+    backups: usize,
+
+    /// Save without being asked: off, change (after every change) or idle:SECONDS
+    #[arg(short, long, default_value = "off", value_name = "WHEN")]
+    autosave: Autosave,
+
+    /// Format of the file, taken from its extension if not given (.txt is todo.txt)
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    file_format: Option<Kind>,
+
+    /// Key config to use instead of ratatatui/keys.toml in the config directory,
+    /// $XDG_CONFIG_HOME or ~/.config
+    #[arg(long, value_name = "FILE")]
+    keys: Option<PathBuf>,
+
+    /// Colors to use: dark, light or mono, styles in ratatatui/theme.toml in the config directory
+    /// are applied on top. NO_COLOR turns colors off only when neither this nor theme.toml is there
+    #[arg(long, value_enum, value_name = "THEME")]
+    theme: Option<Base>,
+
+    /// Print todos as JSON instead of text (subcommands only)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Change the todo list without opening it, for scripts
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
 fn main() -> Result<()> {
-    stdout().execute(EnterAlternateScreen)?;
-    enable_raw_mode()?;
-    let mut terminal: Terminal<CrosstermBackend<std::io::Stdout>> =
-        Terminal::new(CrosstermBackend::new(stdout()))?;
-    terminal.clear()?;
+    let args = Args::parse();
+
+    let kind = args.file_format.unwrap_or_else(|| Kind::of(&args.file));
+    let mut storage = FileStorage::new(args.file, kind.codec(&args.section));
+    storage.backups = args.backups;
+    let mut app = App::new(Box::new(storage));
+    app.autosave = args.autosave;
+
+    // a broken key config is reported before anything else happens, a missing default one
+    // means the built-in keys
+    let keys = args
+        .keys
+        .or_else(|| config::path("keys.toml").filter(|path| path.exists()));
+    if let Some(path) = keys {
+        app.keymap = Keymap::load(&path).unwrap_or_else(|err| {
+            eprintln!("ratatatui: {}: {}", path.display(), err);
+            process::exit(1);
+        });
+    }
+
+    // NO_COLOR (https://no-color.org) is the default, colors the user chose with --theme or
+    // theme.toml are used anyway
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let theme = config::path("theme.toml").filter(|path| path.exists());
+    match theme {
+        Some(path) => {
+            app.theme = Theme::load(&path, args.theme).unwrap_or_else(|err| {
+                eprintln!("ratatatui: {}: {}", path.display(), err);
+                process::exit(1);
+            })
+        }
+        None if no_color && args.theme.is_none() => app.theme = Theme::new(Base::Mono),
+        None => app.theme = Theme::new(args.theme.unwrap_or(Base::Dark)),
+    }
+
+    // load before touching the terminal so errors are printed on a normal screen
+    match app.load() {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound && args.create => {}
+        Err(err) => {
+            eprintln!("ratatatui: {}: {}", app.storage.name(), err);
+            if err.kind() == ErrorKind::NotFound {
+                eprintln!("ratatatui: pass --create to start a new todo list");
+            }
+            process::exit(1);
+        }
+    }
 
-    let mut app = App::new();
+    // subcommands print their result and exit without the TUI
+    if let Some(command) = args.command {
+        let result = cli::run(&mut app, command, args.json).and_then(|output| {
+            if app.is_dirty() {
+                app.save()?;
+            }
+            Ok(output)
+        });
+        match result {
+            Ok(output) => print!("{}", output),
+            Err(err) => {
+                eprintln!("ratatatui: {}", err);
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
-    let _ = app.start(&mut terminal);
+    // the terminal is restored when the guard is dropped, before errors are printed
+    let result = (|| {
+        let _guard = terminal::Guard::enter()?;
+        let mut terminal: Terminal<CrosstermBackend<std::io::Stdout>> =
+            Terminal::new(CrosstermBackend::new(stdout()))?;
+        terminal.clear()?;
+        app.start(&mut terminal)
+    })();
 
-    stdout().execute(LeaveAlternateScreen)?;
-    disable_raw_mode()?;
+    if let Err(err) = result {
+        eprintln!("ratatatui: {}", err);
+        process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,478 @@
+use crate::codec::{Codec, Section};
+use crate::parser::{self, is_fence, Entry};
+use crate::todo::{self, Todo};
+use std::io;
+use std::ops::Range;
+
+// Helpers to find and replace the sections of a markdown document that hold todo lists.
+// A section starts after its heading line and runs up to the next heading of the same or
+// higher level (fewer `#`), or to the end of the document. When several lists are read from
+// one document a section also ends at the heading of another list, so they never overlap.
+
+// Level of an ATX heading (`## Todo` -> 2), None if the line is not a heading
+pub fn heading_level(line: &str) -> Option<usize> {
+    let line = line.trim_start();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    match line[level..].chars().next() {
+        None | Some(' ') | Some('\t') => Some(level),
+        _ => None,
+    }
+}
+
+// Section names without a heading marker default to a level 2 heading
+pub fn heading(section: &str) -> String {
+    let section = section.trim();
+    if section.starts_with('#') {
+        section.to_string()
+    } else {
+        format!("## {}", section)
+    }
+}
+
+// Byte range of the heading line (including its newline) and of the section body. The body
+// stops early at any of the `lists` headings.
+fn locate(doc: &str, heading: &str, lists: &[&str]) -> Option<(Range<usize>, Range<usize>)> {
+    let level = heading_level(heading)?;
+    let mut offset = 0;
+    let mut in_fence = false;
+    let mut found: Option<(Range<usize>, usize)> = None;
+
+    for line in doc.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+
+        if is_fence(text) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        match &found {
+            None => {
+                if text.trim_end() == heading {
+                    found = Some((start..offset, offset));
+                }
+            }
+            Some((head, body_start)) => {
+                let text = text.trim_end();
+                if heading_level(text).is_some_and(|l| l <= level) || lists.contains(&text) {
+                    return Some((head.clone(), *body_start..start));
+                }
+            }
+        }
+    }
+    found.map(|(head, body_start)| (head, body_start..doc.len()))
+}
+
+// Byte range of the body of `heading`, None if the document has no such heading
+pub fn find_section(doc: &str, heading: &str) -> Option<Range<usize>> {
+    locate(doc, heading, &[]).map(|(_, body)| body)
+}
+
+// Headings of the sections with task list items of their own, in document order. A list under
+// a subheading of such a section belongs to it.
+pub fn task_sections(doc: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    // heading whose own lines are being read, and the level of the list we are in
+    let mut current: Option<(&str, usize)> = None;
+    let mut inside: Option<usize> = None;
+    let mut in_fence = false;
+
+    for line in doc.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let text = line.trim_end();
+        if let Some(level) = heading_level(text) {
+            if inside.is_some_and(|l| level <= l) {
+                inside = None;
+            }
+            current = inside.is_none().then_some((text, level));
+            continue;
+        }
+        if let Some((heading, level)) = current {
+            if parser::is_task(line) {
+                if !found.iter().any(|h| h == heading) {
+                    found.push(heading.to_string());
+                }
+                inside = Some(level);
+                current = None;
+            }
+        }
+    }
+    found
+}
+
+// Replace the body of `heading` with `body`, leaving everything before and after the section
+// byte for byte as it was. The body ends early at the `lists` headings. The section is
+// appended to the document if it does not exist.
+pub fn splice_section(doc: &str, heading: &str, lists: &[&str], body: &str) -> String {
+    let body = body.trim_end_matches('\n');
+
+    let Some((head, range)) = locate(doc, heading, lists) else {
+        let mut out = doc.to_string();
+        if !out.is_empty() {
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+        out += heading;
+        out.push('\n');
+        if !body.is_empty() {
+            out += body;
+            out.push('\n');
+        }
+        return out;
+    };
+
+    // keep the blank lines that separated the old body from whatever follows it
+    let old = &doc[range.clone()];
+    let trailing = &old[old.trim_end_matches(['\n', '\r']).len()..];
+
+    let mut out = doc[..head.end].to_string();
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out += body;
+    if !body.is_empty() {
+        if trailing.is_empty() {
+            out.push('\n');
+        } else {
+            out += trailing;
+        }
+    } else if range.end < doc.len() {
+        out += trailing;
+    }
+    out += &doc[range.end..];
+    out
+}
+
+// The todo lists kept in sections of a markdown document, the one under `heading` is opened
+// first
+pub struct Markdown {
+    pub heading: String,
+}
+
+impl Markdown {
+    fn decode_section(&self, doc: &str, heading: &str, lists: &[&str]) -> Section {
+        let (_, range) = locate(doc, heading, lists).expect("heading is in the document");
+        Section {
+            name: heading.to_string(),
+            ..parse_body(&doc[range])
+        }
+    }
+}
+
+impl Codec for Markdown {
+    fn decode(&self, doc: &str) -> io::Result<Vec<Section>> {
+        let mut headings = task_sections(doc);
+        headings.retain(|heading| *heading != self.heading);
+        // even while it has no todos yet
+        if find_section(doc, &self.heading).is_some() {
+            headings.insert(0, self.heading.clone());
+        }
+        if headings.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no \"{}\" section", self.heading),
+            ));
+        }
+        let lists: Vec<&str> = headings.iter().map(String::as_str).collect();
+        Ok(lists
+            .iter()
+            .map(|heading| self.decode_section(doc, heading, &lists))
+            .collect())
+    }
+
+    fn encode(&self, doc: &str, sections: &[Section]) -> String {
+        let lists: Vec<&str> = sections
+            .iter()
+            .map(|section| match section.name.as_str() {
+                "" => self.heading.as_str(),
+                name => name,
+            })
+            .collect();
+        sections
+            .iter()
+            .zip(&lists)
+            .fold(doc.to_string(), |doc, (section, heading)| {
+                splice_section(&doc, heading, &lists, &write_body(section))
+            })
+    }
+
+    fn empty(&self) -> Vec<Section> {
+        vec![Section {
+            name: self.heading.clone(),
+            ..Default::default()
+        }]
+    }
+}
+
+// The todos of a section body, the other lines are kept too. They belong to the todo above
+// them, except for the lines above the list and the ones below it that are not indented.
+pub fn parse_body(text: &str) -> Section {
+    let mut section = Section::default();
+    let mut items: Vec<(usize, Todo)> = Vec::new();
+    for (entry, line) in parser::parse(text).into_iter().zip(text.lines()) {
+        match entry {
+            Entry::Task { indent, done, text } => items.push((
+                indent,
+                Todo {
+                    source: Some(line.to_string()),
+                    ..Todo::parse(&text, done)
+                },
+            )),
+            Entry::Other(line) => match items.last_mut() {
+                Some((_, todo)) => todo.notes.push(line),
+                None => section.passthrough.push(line),
+            },
+        }
+    }
+    if let Some((_, last)) = items.last_mut() {
+        let own = last
+            .notes
+            .iter()
+            .take_while(|line| line.starts_with([' ', '\t']) && !line.trim().is_empty())
+            .count();
+        section.below = last.notes.split_off(own);
+    }
+    section.todos = todo::build(items);
+
+    // blank lines at the end are left to the document around the section
+    let below = &mut section.below;
+    while below.last().is_some_and(|l| l.trim().is_empty()) {
+        below.pop();
+    }
+    if section.todos.is_empty() {
+        let passthrough = &mut section.passthrough;
+        while passthrough.last().is_some_and(|l| l.trim().is_empty()) {
+            passthrough.pop();
+        }
+    }
+    section
+}
+
+// The todos of a section with the other lines where they were
+pub fn write_body(section: &Section) -> String {
+    let mut body = String::new();
+    for line in &section.passthrough {
+        body += &(line.clone() + "\n");
+    }
+    write_todos(&section.todos, None, &mut body);
+    for line in &section.below {
+        body += &(line.clone() + "\n");
+    }
+    body
+}
+
+// `parent` is the indentation its parent was written with
+fn write_todos(todos: &[Todo], parent: Option<usize>, body: &mut String) {
+    let mut previous = None;
+    for todo in todos {
+        let (indent, line) = write_todo(todo, parent, previous);
+        *body += &line;
+        for line in &todo.notes {
+            *body += &(line.clone() + "\n");
+        }
+        write_todos(&todo.children, Some(indent), body);
+        previous = Some(indent);
+    }
+}
+
+// A todo keeps the indentation and list marker it was read with, as long as that still nests
+// it under the same parent, and its line as it was if the todo did not change
+fn write_todo(todo: &Todo, parent: Option<usize>, previous: Option<usize>) -> (usize, String) {
+    let line = todo::line(todo, 0);
+    let new = || previous.unwrap_or(parent.map_or(0, |indent| indent + 2));
+    let source = todo.source.as_deref().unwrap_or_default();
+    let entries = parser::parse(source);
+    let ([Entry::Task { indent, done, text }], Some(start)) =
+        (entries.as_slice(), source.find('['))
+    else {
+        return (new(), " ".repeat(new()) + &line);
+    };
+    let checkbox = if todo::line(&Todo::parse(text, *done), 0) == line {
+        format!("{}\n", &source[start..])
+    } else {
+        line["- ".len()..].to_string()
+    };
+    let fits = parent.is_none_or(|parent| *indent > parent)
+        && previous.is_none_or(|previous| *indent <= previous);
+    if fits {
+        (*indent, source[..start].to_string() + &checkbox)
+    } else {
+        let marker = source[..start].trim_start();
+        (new(), " ".repeat(new()) + marker + &checkbox)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn section_body<'a>(doc: &'a str, heading: &str) -> Option<&'a str> {
+        find_section(doc, heading).map(|range| &doc[range])
+    }
+
+    #[test]
+    fn headings() {
+        assert_eq!(heading_level("## Todo"), Some(2));
+        assert_eq!(heading_level("#"), Some(1));
+        assert_eq!(heading_level("#hashtag"), None);
+        assert_eq!(heading_level("####### too deep"), None);
+        assert_eq!(heading_level("- [ ] item"), None);
+        assert_eq!(heading("Backlog"), "## Backlog");
+        assert_eq!(heading("### Bugs "), "### Bugs");
+    }
+
+    #[test]
+    fn section_ends_at_same_or_higher_heading() {
+        let doc = "# Title\n## Todo\n- [ ] a\n### Sub\n- [ ] b\n## License\nMIT\n";
+        assert_eq!(
+            section_body(doc, "## Todo"),
+            Some("- [ ] a\n### Sub\n- [ ] b\n")
+        );
+        let doc = "## Todo\n- [ ] a\n# Top\n";
+        assert_eq!(section_body(doc, "## Todo"), Some("- [ ] a\n"));
+        assert_eq!(section_body(doc, "## Missing"), None);
+    }
+
+    #[test]
+    fn headings_in_code_blocks_are_ignored() {
+        let doc = "```\n## Todo\n```\n## Todo\n- [ ] a\n```md\n## Fake\n```\n- [ ] b\n## End\n";
+        assert_eq!(
+            section_body(doc, "## Todo"),
+            Some("- [ ] a\n```md\n## Fake\n```\n- [ ] b\n")
+        );
+    }
+
+    #[test]
+    fn splice_keeps_content_before_and_after() {
+        let doc = "# Project\n\nintro\n\n## Todo\n- [ ] old\n\n## License\n\nMIT\n";
+        assert_eq!(
+            splice_section(doc, "## Todo", &[], "- [ ] new\n- [x] done\n"),
+            "# Project\n\nintro\n\n## Todo\n- [ ] new\n- [x] done\n\n## License\n\nMIT\n"
+        );
+    }
+
+    #[test]
+    fn splice_round_trip_is_identity() {
+        let docs = [
+            "## Todo\n- [ ] a\n",
+            "## Todo\n",
+            "# Title\n## Todo\n\n- [ ] a\n\n\n## Changelog\n- v1\n",
+            "## Todo\n- [ ] a\n### Later\n- [ ] b\n# Appendix\n",
+            "## Todo\n## Next\n",
+        ];
+        for doc in docs {
+            let body = section_body(doc, "## Todo").unwrap();
+            assert_eq!(splice_section(doc, "## Todo", &[], body), doc);
+        }
+    }
+
+    #[test]
+    fn splice_terminates_last_line() {
+        assert_eq!(splice_section("## Todo", "## Todo", &[], ""), "## Todo\n");
+        assert_eq!(
+            splice_section("## Todo\n- [ ] a", "## Todo", &[], "- [ ] a"),
+            "## Todo\n- [ ] a\n"
+        );
+    }
+
+    #[test]
+    fn splice_appends_missing_section() {
+        assert_eq!(
+            splice_section("# Readme\ntext", "## Todo", &[], "- [ ] a\n"),
+            "# Readme\ntext\n\n## Todo\n- [ ] a\n"
+        );
+        assert_eq!(splice_section("", "## Todo", &[], ""), "## Todo\n");
+    }
+
+    #[test]
+    fn every_task_list_is_found() {
+        let doc = "# Project\nintro\n- [docs](url)\n## Backlog\n- [ ] a\n### Later\n- [ ] b\n## Notes\n```\n- [ ] code\n```\n## Bugs\n1. [x] c\n";
+        assert_eq!(task_sections(doc), ["## Backlog", "## Bugs"]);
+
+        let lists = Markdown {
+            heading: String::from("## Notes"),
+        }
+        .decode(doc)
+        .unwrap();
+        let names: Vec<&str> = lists.iter().map(|list| list.name.as_str()).collect();
+        assert_eq!(names, ["## Notes", "## Backlog", "## Bugs"]);
+        assert_eq!(lists[1].todos.len(), 2);
+    }
+
+    #[test]
+    fn lists_are_saved_into_their_sections() {
+        let doc = "## Backlog\n- [ ] a\n## Bugs\n- [ ] b\n";
+        let codec = Markdown {
+            heading: String::from("## Todo"),
+        };
+        let mut lists = codec.decode(doc).unwrap();
+        let moved = lists[0].todos.remove(0);
+        lists[1].todos.push(moved);
+        assert_eq!(
+            codec.encode(doc, &lists),
+            "## Backlog\n\n## Bugs\n- [ ] b\n- [ ] a\n"
+        );
+        assert_eq!(
+            codec.decode("# Readme\n").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn lists_do_not_overlap() {
+        let add = |doc: &str, heading: &str, text: &str| {
+            let codec = Markdown {
+                heading: String::from(heading),
+            };
+            let mut lists = codec.decode(doc).unwrap();
+            lists[0].todos.push(Todo::parse(text, false));
+            codec.encode(doc, &lists)
+        };
+
+        // a list right under the title holds the section opened first
+        let doc =
+            "# Project\n- [ ] top level task\n\n## Todo\n- [ ] a\n- [ ] b\n\n## License\nMIT\n";
+        assert_eq!(
+            add(doc, "## Todo", "c"),
+            "# Project\n- [ ] top level task\n\n## Todo\n- [ ] a\n- [ ] b\n- [ ] c\n\n## License\nMIT\n"
+        );
+
+        // the section opened first is a subheading of another list
+        let doc = "## Todo\n- [ ] a\n### Sub\n- [ ] s1\n## End\n";
+        assert_eq!(
+            add(doc, "### Sub", "z"),
+            "## Todo\n- [ ] a\n### Sub\n- [ ] s1\n- [ ] z\n## End\n"
+        );
+    }
+
+    #[test]
+    fn lists_keep_their_markers_and_indentation() {
+        let body = "* [ ] a\n    1. [X]  b\n    1. [ ] c\n\t+ [ ] d\n";
+        let mut section = parse_body(body);
+        assert_eq!(write_body(&section), body);
+
+        // changed todos keep their marker and indentation, moved ones their marker
+        section.todos[0].children[0].text.push_str(" twice");
+        let c = section.todos[0].children.remove(1);
+        section.todos.push(c);
+        assert_eq!(
+            write_body(&section),
+            "* [ ] a\n    1. [x] b twice\n\t+ [ ] d\n1. [ ] c\n"
+        );
+    }
+}
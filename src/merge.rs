@@ -0,0 +1,186 @@
+use crate::todo::{self, Todo};
+use std::collections::{HashMap, HashSet};
+
+// Three-way merge of todo lists, used when the file changed on disk while the app had
+// unsaved changes. Todos are matched by id on every level of the tree: whichever side changed
+// a todo since the common base wins, when both did ours wins. Subtasks are merged the same
+// way, so each side may edit different subtasks of the same todo.
+
+pub fn merge(base: &[Todo], ours: &[Todo], theirs: &[Todo]) -> Vec<Todo> {
+    let mut merged = Vec::new();
+
+    for todo in ours {
+        match (find(base, todo.id), find(theirs, todo.id)) {
+            (Some(before), Some(other)) => merged.push(merge_todo(before, todo, other)),
+            // deleted by them, kept if we changed it since
+            (Some(before), None) if unchanged(before, todo) => {}
+            _ => merged.push(todo.clone()),
+        }
+    }
+
+    for (index, todo) in theirs.iter().enumerate() {
+        if find(ours, todo.id).is_some() {
+            continue;
+        }
+        // deleted by us, kept if they changed it since
+        if find(base, todo.id).is_some_and(|before| unchanged(before, todo)) {
+            continue;
+        }
+        // added by them, it goes after the todo it follows in their list
+        let position = theirs[..index]
+            .iter()
+            .rev()
+            .find_map(|previous| merged.iter().position(|m| m.id == previous.id))
+            .map_or(0, |position| position + 1);
+        merged.insert(position, todo.clone());
+    }
+    merged
+}
+
+fn find(list: &[Todo], id: u64) -> Option<&Todo> {
+    list.iter().find(|todo| todo.id == id)
+}
+
+fn merge_todo(base: &Todo, ours: &Todo, theirs: &Todo) -> Todo {
+    let mut todo = if same(base, ours) {
+        Todo {
+            collapsed: ours.collapsed,
+            ..theirs.clone()
+        }
+    } else {
+        ours.clone()
+    };
+    todo.children = merge(&base.children, &ours.children, &theirs.children);
+    todo
+}
+
+// Equal apart from subtasks and what is never saved
+fn same(a: &Todo, b: &Todo) -> bool {
+    let saved = |todo: &Todo| Todo {
+        children: Vec::new(),
+        collapsed: false,
+        origin: None,
+        id_in_file: false,
+        ..todo.clone()
+    };
+    saved(a) == saved(b)
+}
+
+fn unchanged(before: &Todo, after: &Todo) -> bool {
+    same(before, after)
+        && before.children.len() == after.children.len()
+        && before
+            .children
+            .iter()
+            .zip(&after.children)
+            .all(|(before, after)| unchanged(before, after))
+}
+
+// Todos someone added to the file by hand have no id yet. They get the id of a todo with the
+// same text we know from the base, unless that todo is still in the file with its id.
+pub fn adopt_ids(base: &[Todo], theirs: &mut [Todo]) {
+    let mut present = HashSet::new();
+    todo::for_each(theirs, &mut |todo| {
+        present.insert(todo.id);
+    });
+    let mut by_text = HashMap::new();
+    todo::for_each(base, &mut |todo| {
+        if !present.contains(&todo.id) {
+            by_text.entry(todo.text.clone()).or_insert(todo.id);
+        }
+    });
+    todo::for_each_mut(theirs, &mut |todo| {
+        if todo.id == 0 {
+            if let Some(id) = by_text.remove(&todo.text) {
+                todo.id = id;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn todo(id: u64, text: &str) -> Todo {
+        Todo {
+            id,
+            ..Todo::new(text.to_string(), false)
+        }
+    }
+
+    fn texts(list: &[Todo]) -> Vec<&str> {
+        list.iter().map(|todo| todo.text.as_str()).collect()
+    }
+
+    #[test]
+    fn edits_on_both_sides() {
+        let base = vec![todo(1, "a"), todo(2, "b")];
+        let ours = vec![todo(1, "a ours"), todo(2, "b")];
+        let theirs = vec![todo(1, "a"), todo(2, "b theirs")];
+        assert_eq!(texts(&merge(&base, &ours, &theirs)), ["a ours", "b theirs"]);
+
+        // both changed the same todo, ours wins
+        let theirs = vec![todo(1, "a theirs"), todo(2, "b")];
+        assert_eq!(texts(&merge(&base, &ours, &theirs)), ["a ours", "b"]);
+    }
+
+    #[test]
+    fn additions_keep_their_place() {
+        let base = vec![todo(1, "a"), todo(2, "b")];
+        let ours = vec![todo(1, "a"), todo(2, "b"), todo(3, "ours")];
+        let theirs = vec![
+            todo(4, "first"),
+            todo(1, "a"),
+            todo(5, "after a"),
+            todo(2, "b"),
+        ];
+        assert_eq!(
+            texts(&merge(&base, &ours, &theirs)),
+            ["first", "a", "after a", "b", "ours"]
+        );
+    }
+
+    #[test]
+    fn deletions_unless_changed() {
+        let base = vec![todo(1, "a"), todo(2, "b"), todo(3, "c")];
+        // they deleted a and b, we changed b and deleted c
+        let ours = vec![todo(1, "a"), todo(2, "b ours")];
+        let theirs = vec![todo(3, "c")];
+        assert_eq!(texts(&merge(&base, &ours, &theirs)), ["b ours"]);
+
+        // we deleted a todo they completed
+        let mut done = todo(3, "c");
+        done.set_done(true);
+        assert_eq!(texts(&merge(&base, &[], &[done])), ["c"]);
+    }
+
+    #[test]
+    fn subtasks_are_merged() {
+        let tree = |parent: &str, first: &str, second: &str| {
+            let mut root = todo(1, parent);
+            root.children = vec![todo(2, first), todo(3, second)];
+            vec![root]
+        };
+        let base = tree("root", "x", "y");
+        let mut ours = tree("root", "x ours", "y");
+        ours[0].collapsed = true;
+        let theirs = tree("root theirs", "x", "y theirs");
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged[0].text, "root theirs");
+        assert!(merged[0].collapsed);
+        assert_eq!(texts(&merged[0].children), ["x ours", "y theirs"]);
+    }
+
+    #[test]
+    fn ids_are_adopted_by_text() {
+        let base = vec![todo(1, "a"), todo(2, "b"), todo(3, "b")];
+        let mut theirs = vec![todo(0, "b"), todo(3, "b"), todo(0, "new")];
+        adopt_ids(&base, &mut theirs);
+        assert_eq!(
+            theirs.iter().map(|todo| todo.id).collect::<Vec<_>>(),
+            [2, 3, 0]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// Parser for GitHub flavoured markdown task lists.
+// Accepts `-`, `*`, `+` and numbered (`1.` / `1)`) list items with a `[ ]`, `[x]` or `[X]`
+// checkbox. Every other line is handed back untouched so it can be written out again, that
+// includes items with other states like `[-]` or `[>]` from Obsidian and friends, and broken
+// checkboxes like `- [WIP`.
+
+#[derive(Debug, PartialEq)]
+pub enum Entry {
+    Task {
+        // width of the leading whitespace, tabs count as 4 columns
+        indent: usize,
+        done: bool,
+        text: String,
+    },
+    Other(String),
+}
+
+// Lines in fenced code blocks are never tasks
+pub fn parse(text: &str) -> Vec<Entry> {
+    let mut in_fence = false;
+    text.lines()
+        .map(|line| {
+            if is_fence(line) {
+                in_fence = !in_fence;
+            }
+            if in_fence || is_fence(line) {
+                return Entry::Other(line.to_string());
+            }
+            parse_line(line)
+        })
+        .collect()
+}
+
+// Start or end of a fenced code block
+pub fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+// Whether `line` is a task list item
+pub fn is_task(line: &str) -> bool {
+    matches!(parse_line(line), Entry::Task { .. })
+}
+
+fn parse_line(line: &str) -> Entry {
+    let other = || Entry::Other(line.to_string());
+
+    let content = line.trim_start();
+    let indent = line[..line.len() - content.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+
+    let Some(rest) = strip_marker(content) else {
+        return other();
+    };
+    let Some(rest) = rest.strip_prefix('[') else {
+        return other();
+    };
+
+    let mut chars = rest.chars();
+    // `- [link](url)`, `- [WIP` and friends are ordinary list items
+    let (Some(mark), Some(']')) = (chars.next(), chars.next()) else {
+        return other();
+    };
+    let done = match mark {
+        ' ' => false,
+        'x' | 'X' => true,
+        // states we don't know are kept as they are
+        _ => return other(),
+    };
+
+    let text = &rest[mark.len_utf8() + 1..];
+    if !(text.is_empty() || text.starts_with([' ', '\t'])) {
+        return other();
+    }
+    Entry::Task {
+        indent,
+        done,
+        text: text.trim().to_string(),
+    }
+}
+
+// Strip a bullet (`-`, `*`, `+`) or ordered list marker (`12.`, `3)`) and the space after it
+fn strip_marker(line: &str) -> Option<&str> {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let rest = if digits > 0 {
+        if digits > 9 {
+            return None;
+        }
+        line[digits..].strip_prefix(['.', ')'])?
+    } else {
+        line.strip_prefix(['-', '*', '+'])?
+    };
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(rest.trim_start())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(indent: usize, done: bool, text: &str) -> Entry {
+        Entry::Task {
+            indent,
+            done,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn list_markers() {
+        let entries = parse("- [ ] a\n* [x] b\n+ [X] c\n1. [ ] d\n23) [x] e\n");
+        assert_eq!(
+            entries,
+            vec![
+                task(0, false, "a"),
+                task(0, true, "b"),
+                task(0, true, "c"),
+                task(0, false, "d"),
+                task(0, true, "e"),
+            ]
+        );
+    }
+
+    #[test]
+    fn passthrough_lines() {
+        let text = "intro\n-\n- plain bullet\n- [link](https://example.com)\n-[ ] no space\n- [ ]nospace\nab\n\n### Sub\n- [-] cancelled\n- [>] deferred\n";
+        let entries = parse(text);
+        assert_eq!(entries.len(), 11);
+        for (entry, line) in entries.iter().zip(text.lines()) {
+            assert_eq!(entry, &Entry::Other(line.to_string()));
+        }
+    }
+
+    #[test]
+    fn code_blocks_hold_no_tasks() {
+        let entries = parse("```\n- [ ] x\n- [ b\n```\n- [ ] a\n");
+        assert_eq!(entries[1], Entry::Other(String::from("- [ ] x")));
+        assert_eq!(entries[4], task(0, false, "a"));
+    }
+
+    #[test]
+    fn indentation_and_empty_text() {
+        let entries = parse("  - [ ] two\n\t* [x] tab\n- [ ]\n");
+        assert_eq!(
+            entries,
+            vec![
+                task(2, false, "two"),
+                task(4, true, "tab"),
+                task(0, false, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_checkboxes_are_passthrough() {
+        let text = "- [ ] fine\n- [WIP rewrite\n- [?\n1. [\n- [ b";
+        let entries = parse(text);
+        assert_eq!(entries[0], task(0, false, "fine"));
+        for (entry, line) in entries[1..].iter().zip(text.lines().skip(1)) {
+            assert_eq!(entry, &Entry::Other(line.to_string()));
+        }
+    }
+}
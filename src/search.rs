@@ -0,0 +1,31 @@
+// Fuzzy matching for `/`: the characters of the query have to appear in the text in the same
+// order, but not next to each other. `dcs` finds "write docs". Case is ignored.
+
+// Byte offsets of the characters of `text` matching `query`, None if it does not match
+pub fn find(query: &str, text: &str) -> Option<Vec<usize>> {
+    let mut found = Vec::new();
+    let mut chars = text.char_indices();
+    for wanted in query.chars() {
+        let (index, _) = chars.find(|(_, c)| same(*c, wanted))?;
+        found.push(index);
+    }
+    Some(found)
+}
+
+fn same(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(find("dcs", "write docs"), Some(vec![6, 8, 9]));
+        assert_eq!(find("WD", "write docs"), Some(vec![0, 6]));
+        assert_eq!(find("äb", "Äpfel kaufen #bio"), Some(vec![0, 15]));
+        assert_eq!(find("sd", "write docs"), None);
+        assert_eq!(find("", "anything"), Some(vec![]));
+    }
+}
@@ -1,15 +1,23 @@
+use std::str::FromStr;
+use std::time::Duration;
+
 #[derive(Debug)]
 pub struct State {
     pub mode: Mode,
     pub active: Screen,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Mode {
     READ, //default mode read mode
     EDIT,
     INSERT,
     GRAB,
+    CONFIRM, // waiting for an answer to App::confirm
+    COMMAND, // typing a `:` command
+    SEARCH,  // typing a `/` search
+    VISUAL,  // selecting several todos
 }
 
 impl Mode {
@@ -19,11 +27,51 @@ impl Mode {
             Mode::GRAB => "GRAB MODE",
             Mode::INSERT => "INSERT MODE",
             Mode::READ => "READ MODE",
+            Mode::CONFIRM => "CONFIRM",
+            Mode::COMMAND => "COMMAND MODE",
+            Mode::SEARCH => "SEARCH MODE",
+            Mode::VISUAL => "VISUAL MODE",
         }
     }
 }
 
 #[derive(Debug, PartialEq)]
+pub enum Confirm {
+    CompleteChildren(usize), // number of unfinished subtasks
+    Delete(String, usize),   // text and number of subtasks
+    DeleteSelection(usize),  // number of todos selected in VISUAL mode
+    SaveFailed(String),      // why the file could not be written
+    Conflict,                // the file changed on disk
+}
+
+impl Confirm {
+    pub fn get(&self) -> String {
+        match self {
+            Confirm::CompleteChildren(count) => {
+                format!(
+                    "Complete {} unfinished subtask(s) too? (y/n, Esc to cancel)",
+                    count
+                )
+            }
+            Confirm::Delete(text, 0) => format!("Delete \"{}\"? (y/n)", text),
+            Confirm::Delete(text, subtasks) => {
+                format!("Delete \"{}\" and {} subtask(s)? (y/n)", text, subtasks)
+            }
+            Confirm::DeleteSelection(count) => {
+                format!("Delete {} selected todo(s) and their subtasks? (y/n)", count)
+            }
+            Confirm::SaveFailed(err) => {
+                format!("Saving failed: {}. Quit and lose changes? (y/n)", err)
+            }
+            Confirm::Conflict => String::from(
+                "The file changed on disk: r to reload, o to overwrite, m to merge, Esc to decide later",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Screen {
     COMPLETED, //Allows only edit and read
     ACTIVE,    //Allows every mode
@@ -37,3 +85,46 @@ impl Screen {
         }
     }
 }
+
+// When changes are written without being asked to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Autosave {
+    Off,
+    Change,         // as soon as a change is finished
+    Idle(Duration), // once no key was pressed for a while
+}
+
+impl FromStr for Autosave {
+    type Err = String;
+
+    // "off", "change" or "idle:SECONDS"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "off" => Ok(Autosave::Off),
+            None if s == "change" => Ok(Autosave::Change),
+            Some(("idle", seconds)) => match seconds.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Ok(Autosave::Idle(Duration::from_secs(seconds))),
+                _ => Err(format!("\"{}\" is not a number of seconds", seconds)),
+            },
+            _ => Err(String::from("expected off, change or idle:SECONDS")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_autosave() {
+        assert_eq!("off".parse(), Ok(Autosave::Off));
+        assert_eq!("change".parse(), Ok(Autosave::Change));
+        assert_eq!(
+            "idle:30".parse(),
+            Ok(Autosave::Idle(Duration::from_secs(30)))
+        );
+        assert!("idle:0".parse::<Autosave>().is_err());
+        assert!("idle".parse::<Autosave>().is_err());
+        assert!("always".parse::<Autosave>().is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::codec::{Codec, Section};
+use crate::file;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Where the todo lists are kept. The app only sees lists of todos, reading and writing them,
+// and exports of them, and noticing when someone else changes them is up to the storage.
+
+pub trait Storage {
+    // Where the todos are kept, for messages
+    fn name(&self) -> String;
+
+    // Err with NotFound if there is no todo list yet, saving creates it
+    fn load(&mut self) -> io::Result<Vec<Section>>;
+
+    fn save(&mut self, sections: &[Section]) -> io::Result<()>;
+
+    // The lists to start with when there is nothing to load
+    fn empty(&self) -> Vec<Section>;
+
+    // The lists someone else saved since we last loaded or saved, None if there are none or
+    // the storage can't tell
+    fn watch(&self) -> io::Result<Option<Vec<Section>>> {
+        Ok(None)
+    }
+
+    // Keep an export of the todos at `path`
+    fn export(&self, path: &Path, text: &str) -> io::Result<()>;
+
+    // An export kept at `path`
+    fn import(&self, path: &Path) -> io::Result<String>;
+}
+
+// A file, with the todos read and written by `codec`
+pub struct FileStorage {
+    pub path: PathBuf,
+    pub codec: Box<dyn Codec>,
+    // number of `.bak` copies kept of the file, 0 for none
+    pub backups: usize,
+    // the file as it was last loaded or saved, None if it did not exist
+    disk: Option<String>,
+}
+
+impl FileStorage {
+    pub fn new(path: PathBuf, codec: Box<dyn Codec>) -> Self {
+        FileStorage {
+            path,
+            codec,
+            backups: 0,
+            disk: None,
+        }
+    }
+}
+
+impl Storage for FileStorage {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn load(&mut self) -> io::Result<Vec<Section>> {
+        let doc = fs::read_to_string(&self.path)?;
+        let section = self.codec.decode(&doc);
+        // known even without todos in it, so saving into it later is not a conflict. A file
+        // we failed to read stays a change someone else made.
+        match &section {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {}
+            _ => self.disk = Some(doc),
+        }
+        section
+    }
+
+    // reading file directly to variables as i know my readme files are not going to be huge
+    fn save(&mut self, sections: &[Section]) -> io::Result<()> {
+        let doc = match fs::read_to_string(&self.path) {
+            Ok(doc) => doc,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let doc = self.codec.encode(&doc, sections);
+        file::backup(&self.path, self.backups)?;
+        file::write_atomic(&self.path, &doc)?;
+        self.disk = Some(doc);
+        Ok(())
+    }
+
+    fn empty(&self) -> Vec<Section> {
+        self.codec.empty()
+    }
+
+    // A file that went away is not a change, saving writes it again
+    fn watch(&self) -> io::Result<Option<Vec<Section>>> {
+        match fs::read_to_string(&self.path) {
+            Ok(now) if self.disk.as_ref() != Some(&now) => self.codec.decode(&now).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn export(&self, path: &Path, text: &str) -> io::Result<()> {
+        file::write_atomic(path, text)
+    }
+
+    fn import(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+// Todos kept in memory, clones share them so tests can play someone else saving
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct Memory {
+    stored: std::rc::Rc<std::cell::RefCell<Option<Vec<Section>>>>,
+    // what we last loaded or saved
+    seen: Option<Vec<Section>>,
+    exports: std::rc::Rc<std::cell::RefCell<std::collections::HashMap<PathBuf, String>>>,
+}
+
+#[cfg(test)]
+impl Memory {
+    pub fn get(&self) -> Option<Vec<Section>> {
+        self.stored.borrow().clone()
+    }
+
+    // Save as someone else
+    pub fn set(&self, sections: Vec<Section>) {
+        *self.stored.borrow_mut() = Some(sections);
+    }
+
+    pub fn exported(&self, path: &Path) -> Option<String> {
+        self.exports.borrow().get(path).cloned()
+    }
+}
+
+#[cfg(test)]
+impl Storage for Memory {
+    fn name(&self) -> String {
+        String::from("memory")
+    }
+
+    fn load(&mut self) -> io::Result<Vec<Section>> {
+        self.seen = self.get();
+        self.seen
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "nothing saved yet"))
+    }
+
+    fn save(&mut self, sections: &[Section]) -> io::Result<()> {
+        self.set(sections.to_vec());
+        self.seen = Some(sections.to_vec());
+        Ok(())
+    }
+
+    fn empty(&self) -> Vec<Section> {
+        vec![Section {
+            name: String::from("## Todo"),
+            ..Default::default()
+        }]
+    }
+
+    fn watch(&self) -> io::Result<Option<Vec<Section>>> {
+        let now = self.get();
+        Ok(now.filter(|now| self.seen.as_ref() != Some(now)))
+    }
+
+    fn export(&self, path: &Path, text: &str) -> io::Result<()> {
+        self.exports
+            .borrow_mut()
+            .insert(path.to_path_buf(), text.to_string());
+        Ok(())
+    }
+
+    fn import(&self, path: &Path) -> io::Result<String> {
+        self.exported(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such export"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codec::Kind;
+    use crate::todo::Todo;
+
+    fn section(text: &str) -> Vec<Section> {
+        vec![Section {
+            name: String::from("## Todo"),
+            passthrough: Vec::new(),
+            below: Vec::new(),
+            todos: vec![Todo {
+                source: Some(format!("- [ ] {}", text)),
+                ..Todo::new(text.to_string(), false)
+            }],
+        }]
+    }
+
+    #[test]
+    fn file_keeps_the_rest_of_the_document() {
+        let path =
+            std::env::temp_dir().join(format!("ratatatui-{}-storage.md", std::process::id()));
+        fs::write(&path, "# Notes\nkeep me\n## Todo\n- [ ] a\n").unwrap();
+        let mut storage = FileStorage::new(path.clone(), Kind::Markdown.codec("## Todo"));
+
+        assert_eq!(storage.load().unwrap(), section("a"));
+        storage.save(&section("b")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Notes\nkeep me\n## Todo\n- [ ] b\n"
+        );
+        assert_eq!(storage.watch().unwrap(), None);
+
+        fs::write(&path, "## Todo\n- [ ] c\n").unwrap();
+        assert_eq!(storage.watch().unwrap(), Some(section("c")));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(storage.watch().unwrap(), None);
+    }
+
+    #[test]
+    fn memory_notices_other_saves() {
+        let mut storage = Memory::default();
+        assert_eq!(storage.load().unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        storage.save(&section("a")).unwrap();
+        assert_eq!(storage.watch().unwrap(), None);
+        storage.clone().set(section("b"));
+        assert_eq!(storage.watch().unwrap(), Some(section("b")));
+        assert_eq!(storage.load().unwrap(), section("b"));
+    }
+}
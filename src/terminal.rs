@@ -0,0 +1,43 @@
+use crossterm::{
+    cursor::Show,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use std::io::{self, stdout};
+use std::panic;
+
+// Raw mode and the alternate screen have to be left again however the app ends, otherwise the
+// user's shell is unusable. The guard restores the terminal when it goes out of scope, also
+// while unwinding, and the panic hook does so before the panic message is printed so the
+// message ends up on the normal screen.
+
+pub struct Guard;
+
+impl Guard {
+    pub fn enter() -> io::Result<Guard> {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info);
+        }));
+
+        // from here on dropping the guard undoes whatever part already succeeded
+        let guard = Guard;
+        stdout().execute(EnterAlternateScreen)?;
+        enable_raw_mode()?;
+        Ok(guard)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// Errors are ignored, there is nothing left to do about them
+fn restore() {
+    let _ = disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+    let _ = stdout().execute(Show);
+}
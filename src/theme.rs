@@ -0,0 +1,213 @@
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use std::path::Path;
+use std::{fs, io};
+
+// How the app looks. A theme starts from one of the built-in ones and `theme.toml` in the
+// config directory (see `config::path`) may change any of its styles:
+//
+//     base = "light"
+//     selected = "black on yellow bold"
+//     completed = "gray crossed_out"
+//
+// A style is a foreground color, `on` and a background color and modifiers, each optional.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Base {
+    // colored text on a dark terminal
+    Dark,
+    // dark text on a light terminal
+    Light,
+    // no colors at all, the selection in reverse video. Used when NO_COLOR is set and no theme was chosen
+    Mono,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub border: Style,
+    // the panel of the screen that takes keys
+    pub focused_border: Style,
+    // the tab of the open todo list
+    pub tab: Style,
+    pub item: Style,
+    pub selected: Style,
+    // todos selected in VISUAL mode
+    pub marked: Style,
+    pub completed: Style,
+    // characters matching the search
+    pub matched: Style,
+    pub status: Style,
+    // `READ MODE`, `INSERT MODE`, .. in the status bar
+    pub mode: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::new(Base::Dark)
+    }
+}
+
+impl Theme {
+    pub fn new(base: Base) -> Self {
+        match base {
+            Base::Dark => Theme {
+                border: Style::new().cyan().bold(),
+                focused_border: Style::new().white().bold(),
+                tab: Style::new().white().reversed(),
+                item: Style::new().cyan(),
+                selected: Style::new().white(),
+                marked: Style::new().black().on_cyan(),
+                completed: Style::new().cyan(),
+                matched: Style::new().yellow().bold(),
+                status: Style::new().bold(),
+                mode: Style::new().bold(),
+            },
+            Base::Light => Theme {
+                border: Style::new().blue().bold(),
+                focused_border: Style::new().black().bold(),
+                tab: Style::new().white().on_blue(),
+                item: Style::new().black(),
+                selected: Style::new().white().on_blue(),
+                marked: Style::new().black().on_light_yellow(),
+                completed: Style::new().dark_gray(),
+                matched: Style::new().red().bold(),
+                status: Style::new().black(),
+                mode: Style::new().magenta().bold(),
+            },
+            Base::Mono => Theme {
+                border: Style::new(),
+                focused_border: Style::new().bold(),
+                tab: Style::new().reversed(),
+                item: Style::new(),
+                selected: Style::new().reversed(),
+                marked: Style::new().underlined(),
+                completed: Style::new().dim(),
+                matched: Style::new().bold(),
+                status: Style::new(),
+                mode: Style::new().bold(),
+            },
+        }
+    }
+
+    // The theme of the config file at `path`, `base` replaces the one the file starts from
+    pub fn load(path: &Path, base: Option<Base>) -> io::Result<Theme> {
+        let text = fs::read_to_string(path)?;
+        parse(&text, base).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn style_mut(&mut self, name: &str) -> Option<&mut Style> {
+        let style = match name {
+            "border" => &mut self.border,
+            "focused_border" => &mut self.focused_border,
+            "tab" => &mut self.tab,
+            "item" => &mut self.item,
+            "selected" => &mut self.selected,
+            "marked" => &mut self.marked,
+            "completed" => &mut self.completed,
+            "matched" => &mut self.matched,
+            "status" => &mut self.status,
+            "mode" => &mut self.mode,
+            _ => return None,
+        };
+        Some(style)
+    }
+}
+
+fn parse(text: &str, base: Option<Base>) -> Result<Theme, String> {
+    let table: toml::Table = text
+        .parse()
+        .map_err(|err: toml::de::Error| err.message().to_string())?;
+    let named = match table.get("base") {
+        Some(name) => Some(
+            name.as_str()
+                .and_then(|name| clap::ValueEnum::from_str(name, true).ok())
+                .ok_or_else(|| String::from("base: one of dark, light or mono"))?,
+        ),
+        None => None,
+    };
+
+    let mut theme = Theme::new(base.or(named).unwrap_or(Base::Dark));
+    for (name, value) in table.iter().filter(|(name, _)| *name != "base") {
+        let at = |err: String| format!("{}: {}", name, err);
+        let style = theme
+            .style_mut(name)
+            .ok_or_else(|| at(String::from("no such style")))?;
+        let value = value
+            .as_str()
+            .ok_or_else(|| at(String::from("styles are strings")))?;
+        *style = parse_style(value).map_err(at)?;
+    }
+    Ok(theme)
+}
+
+// `[color] [on color] [modifier ..]`
+fn parse_style(text: &str) -> Result<Style, String> {
+    let mut style = Style::new();
+    let mut words = text.split_whitespace().peekable();
+    if let Some(color) = words.next_if(|word| word.parse::<Color>().is_ok()) {
+        style = style.fg(color.parse().unwrap());
+    }
+    if words.next_if_eq(&"on").is_some() {
+        let color = words.next().unwrap_or_default();
+        let color: Color = color
+            .parse()
+            .map_err(|_| format!("unknown color \"{}\"", color))?;
+        style = style.bg(color);
+    }
+    for word in words {
+        let modifier = match word {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "crossed_out" => Modifier::CROSSED_OUT,
+            _ => return Err(format!("unknown color or modifier \"{}\"", word)),
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn styles() {
+        assert_eq!(
+            parse_style("black on light-yellow bold").unwrap(),
+            Style::new().black().on_light_yellow().bold()
+        );
+        assert_eq!(
+            parse_style("on #ff0000").unwrap(),
+            Style::new().bg(Color::Rgb(255, 0, 0))
+        );
+        assert_eq!(parse_style("").unwrap(), Style::new());
+        assert_eq!(
+            parse_style("red blinking").unwrap_err(),
+            "unknown color or modifier \"blinking\""
+        );
+    }
+
+    #[test]
+    fn config_changes_the_base() {
+        let theme = parse("base = \"light\"\nselected = \"reversed\"\n", None).unwrap();
+        assert_eq!(theme.selected, Style::new().reversed());
+        assert_eq!(theme.border, Theme::new(Base::Light).border);
+        assert_eq!(parse("", None).unwrap(), Theme::default());
+        // asked for on the command line
+        assert_eq!(
+            parse("base = \"light\"", Some(Base::Mono)).unwrap(),
+            Theme::new(Base::Mono)
+        );
+
+        assert_eq!(
+            parse("selection = \"red\"", None).unwrap_err(),
+            "selection: no such style"
+        );
+        assert_eq!(
+            parse("base = \"solarized\"", None).unwrap_err(),
+            "base: one of dark, light or mono"
+        );
+    }
+}
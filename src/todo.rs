@@ -0,0 +1,488 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+// Todos form a tree: every todo can hold subtasks, which are written as indented list items.
+// Lists are stored as their top level todos, screens work on the flattened visible rows.
+//
+// Metadata is kept in an html comment at the end of the item so github does not render it:
+// `- [x] write docs <!-- id:4 pri:A tags:docs,web created:2024-05-01T10:00:00Z done:... -->`
+// Items written by hand keep their line as it is until there is more than an id to store.
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Todo {
+    // unique within a file, 0 until the app hands one out
+    pub id: u64,
+    // the id was read from the file, it is written back even without other metadata
+    pub id_in_file: bool,
+    pub text: String,
+    pub done: bool,
+    pub created: Option<DateTime<Utc>>,
+    pub completed: Option<DateTime<Utc>>,
+    // todo.txt style priority, 'A' is the highest
+    pub priority: Option<char>,
+    pub tags: Vec<String>,
+    // lines below the item that are not todos, up to the next item
+    pub notes: Vec<String>,
+    // the line it was read from, it is written back as it was while the todo is unchanged
+    pub source: Option<String>,
+    pub children: Vec<Todo>,
+    // hide the children in the TODOS / COMPLETED panels
+    pub collapsed: bool,
+    // index in the active list before it was completed, not saved
+    pub origin: Option<usize>,
+}
+
+impl Todo {
+    pub fn new(text: String, done: bool) -> Self {
+        Todo {
+            text,
+            done,
+            ..Default::default()
+        }
+    }
+
+    // Parse the text of a markdown item, splitting off the metadata comment
+    pub fn parse(text: &str, done: bool) -> Self {
+        let todo = Todo::new(text.to_string(), done);
+        let Some(body) = text.strip_suffix("-->") else {
+            return todo;
+        };
+        let Some(start) = body.rfind("<!--") else {
+            return todo;
+        };
+
+        let fields: Vec<&str> = body[start + 4..].split_whitespace().collect();
+        if fields.is_empty() {
+            return todo;
+        }
+        let mut meta = Todo::new(text[..start].trim_end().to_string(), done);
+        for field in fields {
+            let Some((key, value)) = field.split_once(':') else {
+                return todo;
+            };
+            let parsed = match key {
+                "id" => value
+                    .parse()
+                    .map(|id| {
+                        meta.id = id;
+                        meta.id_in_file = true;
+                    })
+                    .is_ok(),
+                "pri" => parse_priority(value)
+                    .map(|p| meta.priority = Some(p))
+                    .is_some(),
+                "tags" => {
+                    meta.tags = value.split(',').map(String::from).collect();
+                    true
+                }
+                "created" => parse_time(value).map(|t| meta.created = Some(t)).is_some(),
+                "done" => parse_time(value)
+                    .map(|t| meta.completed = Some(t))
+                    .is_some(),
+                _ => false,
+            };
+            // a comment that is not ours belongs to the text
+            if !parsed {
+                return todo;
+            }
+        }
+        meta
+    }
+
+    // Text of the markdown item, with the metadata comment when there is any
+    pub fn markdown(&self) -> String {
+        let mut fields = Vec::new();
+        if let Some(priority) = self.priority {
+            fields.push(format!("pri:{}", priority));
+        }
+        if !self.tags.is_empty() {
+            fields.push(format!("tags:{}", self.tags.join(",")));
+        }
+        if let Some(created) = self.created {
+            fields.push(format!("created:{}", format_time(created)));
+        }
+        if let Some(completed) = self.completed {
+            fields.push(format!("done:{}", format_time(completed)));
+        }
+        if self.id != 0 && (self.id_in_file || !fields.is_empty()) {
+            fields.insert(0, format!("id:{}", self.id));
+        }
+
+        if fields.is_empty() {
+            self.text.clone()
+        } else {
+            format!("{} <!-- {} -->", self.text, fields.join(" "))
+        }
+    }
+
+    // Mark as (not) done, remembering when it was finished
+    pub fn set_done(&mut self, done: bool) {
+        if done && !self.done {
+            self.completed = Some(Utc::now());
+        } else if !done {
+            self.completed = None;
+        }
+        self.done = done;
+    }
+
+    // number of subtasks (at any depth) that are not done yet
+    pub fn pending_children(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| usize::from(!child.done) + child.pending_children())
+            .sum()
+    }
+
+    pub fn set_done_recursive(&mut self, done: bool) {
+        self.set_done(done);
+        self.children
+            .iter_mut()
+            .for_each(|child| child.set_done_recursive(done));
+    }
+}
+
+pub fn parse_priority(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+        _ => None,
+    }
+}
+
+pub fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Visit every todo of a list, subtasks included
+pub fn for_each_mut(list: &mut [Todo], f: &mut impl FnMut(&mut Todo)) {
+    for todo in list {
+        f(todo);
+        for_each_mut(&mut todo.children, f);
+    }
+}
+
+pub fn for_each(list: &[Todo], f: &mut impl FnMut(&Todo)) {
+    for todo in list {
+        f(todo);
+        for_each(&todo.children, f);
+    }
+}
+
+// A visible line of a list: where the todo lives in the tree and how deep it is
+#[derive(Debug, PartialEq)]
+pub struct Row<'a> {
+    pub path: Vec<usize>,
+    pub todo: &'a Todo,
+}
+
+impl Row<'_> {
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+}
+
+// Flatten a list into the rows that are on screen, children of collapsed todos are skipped
+pub fn rows(list: &[Todo]) -> Vec<Row<'_>> {
+    fn walk<'a>(list: &'a [Todo], path: &mut Vec<usize>, rows: &mut Vec<Row<'a>>) {
+        for (index, todo) in list.iter().enumerate() {
+            path.push(index);
+            rows.push(Row {
+                path: path.clone(),
+                todo,
+            });
+            if !todo.collapsed {
+                walk(&todo.children, path, rows);
+            }
+            path.pop();
+        }
+    }
+    let mut result = Vec::new();
+    walk(list, &mut Vec::new(), &mut result);
+    result
+}
+
+// Path of the `row`th visible row
+pub fn path_of(list: &[Todo], row: usize) -> Option<Vec<usize>> {
+    rows(list).into_iter().nth(row).map(|row| row.path)
+}
+
+// Visible row of the todo at `path`
+pub fn row_of(list: &[Todo], path: &[usize]) -> Option<usize> {
+    rows(list).iter().position(|row| row.path == path)
+}
+
+// Path of the todo with `id`, collapsed subtasks included
+pub fn find_id(list: &[Todo], id: u64) -> Option<Vec<usize>> {
+    for (index, todo) in list.iter().enumerate() {
+        if todo.id == id {
+            return Some(vec![index]);
+        }
+        if let Some(mut path) = find_id(&todo.children, id) {
+            path.insert(0, index);
+            return Some(path);
+        }
+    }
+    None
+}
+
+pub fn get_mut<'a>(list: &'a mut [Todo], path: &[usize]) -> Option<&'a mut Todo> {
+    let (first, rest) = path.split_first()?;
+    let todo = list.get_mut(*first)?;
+    if rest.is_empty() {
+        Some(todo)
+    } else {
+        get_mut(&mut todo.children, rest)
+    }
+}
+
+// The list holding the todo at `path`, i.e. the top level list or its parent's children
+pub fn siblings_mut<'a>(list: &'a mut Vec<Todo>, path: &[usize]) -> &'a mut Vec<Todo> {
+    match path.split_last() {
+        Some((_, [])) | None => list,
+        Some((_, parent)) => &mut get_mut(list, parent).expect("invalid todo path").children,
+    }
+}
+
+pub fn remove(list: &mut Vec<Todo>, path: &[usize]) -> Todo {
+    let index = *path.last().expect("empty todo path");
+    siblings_mut(list, path).remove(index)
+}
+
+// Swap the todo at `path` with its next (`down`) or previous sibling, wrapping around.
+// Returns the new path of the moved todo.
+pub fn swap_sibling(list: &mut Vec<Todo>, path: &[usize], down: bool) -> Vec<usize> {
+    let siblings = siblings_mut(list, path);
+    let index = *path.last().expect("empty todo path");
+    let len = siblings.len();
+    let other = if down {
+        (index + 1) % len
+    } else {
+        (index + len - 1) % len
+    };
+    siblings.swap(index, other);
+    let mut moved = path.to_vec();
+    *moved.last_mut().unwrap() = other;
+    moved
+}
+
+// Make the todo a subtask of the sibling above it. Returns its new path.
+pub fn indent(list: &mut Vec<Todo>, path: &[usize]) -> Option<Vec<usize>> {
+    let index = *path.last()?;
+    if index == 0 {
+        return None;
+    }
+    let todo = remove(list, path);
+    let siblings = siblings_mut(list, path);
+    let parent = &mut siblings[index - 1];
+    parent.collapsed = false;
+    parent.children.push(todo);
+
+    let mut moved = path.to_vec();
+    *moved.last_mut().unwrap() = index - 1;
+    moved.push(parent.children.len() - 1);
+    Some(moved)
+}
+
+// Move a subtask up one level, right below its parent. Returns its new path.
+pub fn outdent(list: &mut Vec<Todo>, path: &[usize]) -> Option<Vec<usize>> {
+    if path.len() < 2 {
+        return None;
+    }
+    let todo = remove(list, path);
+    let parent = &path[..path.len() - 1];
+    let index = parent[parent.len() - 1] + 1;
+    siblings_mut(list, parent).insert(index, todo);
+
+    let mut moved = parent.to_vec();
+    *moved.last_mut().unwrap() = index;
+    Some(moved)
+}
+
+// Build a forest out of todos tagged with their indentation width
+pub fn build(items: Vec<(usize, Todo)>) -> Vec<Todo> {
+    let mut list: Vec<Todo> = Vec::new();
+    // indentation and path of the todos new items can be nested under
+    let mut stack: Vec<(usize, Vec<usize>)> = Vec::new();
+
+    for (indent, todo) in items {
+        while stack.last().is_some_and(|(i, _)| *i >= indent) {
+            stack.pop();
+        }
+        let path = match stack.last() {
+            None => {
+                list.push(todo);
+                vec![list.len() - 1]
+            }
+            Some((_, parent)) => {
+                let parent_todo = get_mut(&mut list, parent).unwrap();
+                parent_todo.children.push(todo);
+                let mut path = parent.clone();
+                path.push(parent_todo.children.len() - 1);
+                path
+            }
+        };
+        stack.push((indent, path));
+    }
+    list
+}
+
+// A todo as a markdown task list item, indented for a subtask at `depth`
+pub fn line(todo: &Todo, depth: usize) -> String {
+    let mark = if todo.done { 'x' } else { ' ' };
+    format!("{}- [{}] {}\n", "  ".repeat(depth), mark, todo.markdown())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn todo(text: &str, children: Vec<Todo>) -> Todo {
+        Todo {
+            text: text.to_string(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    fn texts(list: &[Todo]) -> Vec<(usize, &str)> {
+        rows(list)
+            .iter()
+            .map(|row| (row.depth(), row.todo.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn build_nests_by_indentation() {
+        let list = build(vec![
+            (0, todo("a", vec![])),
+            (2, todo("a.1", vec![])),
+            (4, todo("a.1.1", vec![])),
+            (2, todo("a.2", vec![])),
+            (0, todo("b", vec![])),
+            (4, todo("b.1", vec![])),
+        ]);
+        assert_eq!(
+            texts(&list),
+            vec![
+                (0, "a"),
+                (1, "a.1"),
+                (2, "a.1.1"),
+                (1, "a.2"),
+                (0, "b"),
+                (1, "b.1")
+            ]
+        );
+    }
+
+    #[test]
+    fn collapsed_children_are_hidden() {
+        let mut list = vec![todo("a", vec![todo("a.1", vec![])]), todo("b", vec![])];
+        list[0].collapsed = true;
+        assert_eq!(texts(&list), vec![(0, "a"), (0, "b")]);
+        assert_eq!(path_of(&list, 1), Some(vec![1]));
+        assert_eq!(row_of(&list, &[0, 0]), None);
+    }
+
+    #[test]
+    fn indent_and_outdent() {
+        let mut list = vec![todo("a", vec![todo("a.1", vec![])]), todo("b", vec![])];
+        assert_eq!(indent(&mut list, &[0]), None);
+        assert_eq!(indent(&mut list, &[1]), Some(vec![0, 1]));
+        assert_eq!(texts(&list), vec![(0, "a"), (1, "a.1"), (1, "b")]);
+
+        assert_eq!(outdent(&mut list, &[0, 0]), Some(vec![1]));
+        assert_eq!(texts(&list), vec![(0, "a"), (1, "b"), (0, "a.1")]);
+        assert_eq!(outdent(&mut list, &[1]), None);
+    }
+
+    #[test]
+    fn swap_stays_among_siblings() {
+        let mut list = vec![todo("a", vec![todo("a.1", vec![]), todo("a.2", vec![])])];
+        assert_eq!(swap_sibling(&mut list, &[0, 1], true), vec![0, 0]);
+        assert_eq!(texts(&list), vec![(0, "a"), (1, "a.2"), (1, "a.1")]);
+    }
+
+    #[test]
+    fn pending_children_counts_every_level() {
+        let mut list = build(vec![
+            (0, todo("a", vec![])),
+            (2, todo("a.1", vec![])),
+            (4, todo("a.1.1", vec![])),
+        ]);
+        assert_eq!(list[0].pending_children(), 2);
+        list[0].set_done_recursive(true);
+        assert_eq!(list[0].pending_children(), 0);
+        assert!(list[0].children[0].children[0].completed.is_some());
+
+        for_each_mut(&mut list, &mut |todo| todo.completed = None);
+        let out: String = rows(&list)
+            .iter()
+            .map(|row| line(row.todo, row.depth()))
+            .collect();
+        assert_eq!(out, "- [x] a\n  - [x] a.1\n    - [x] a.1.1\n");
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let text = "ship it <!-- id:7 pri:B tags:work,release created:2024-05-01T10:00:00Z done:2024-05-02T08:30:00Z -->";
+        let todo = Todo::parse(text, true);
+        assert_eq!(todo.text, "ship it");
+        assert_eq!(todo.id, 7);
+        assert_eq!(todo.priority, Some('B'));
+        assert_eq!(todo.tags, vec!["work", "release"]);
+        assert_eq!(format_time(todo.created.unwrap()), "2024-05-01T10:00:00Z");
+        assert_eq!(todo.markdown(), text);
+    }
+
+    #[test]
+    fn ids_alone_are_not_added() {
+        let mut todo = Todo::parse("a", false);
+        todo.id = 3;
+        assert_eq!(todo.markdown(), "a");
+        todo.priority = Some('A');
+        assert_eq!(todo.markdown(), "a <!-- id:3 pri:A -->");
+        assert_eq!(
+            Todo::parse("b <!-- id:4 -->", false).markdown(),
+            "b <!-- id:4 -->"
+        );
+    }
+
+    #[test]
+    fn foreign_comments_stay_in_text() {
+        for text in [
+            "a <!-- just a note -->",
+            "b <!-- id:x -->",
+            "c -->",
+            "d <!-- pri:AB -->",
+        ] {
+            let todo = Todo::parse(text, false);
+            assert_eq!(todo.text, text);
+            assert_eq!(todo.markdown(), text);
+        }
+    }
+
+    #[test]
+    fn completion_time_follows_done() {
+        let mut todo = Todo::new(String::from("a"), false);
+        todo.set_done(true);
+        assert!(todo.completed.is_some());
+        todo.set_done(false);
+        assert_eq!(todo.completed, None);
+    }
+
+    #[test]
+    fn find_by_id() {
+        let mut list = vec![todo("a", vec![todo("a.1", vec![])]), todo("b", vec![])];
+        list[0].collapsed = true;
+        list[0].children[0].id = 7;
+        list[1].id = 3;
+        assert_eq!(find_id(&list, 7), Some(vec![0, 0]));
+        assert_eq!(find_id(&list, 3), Some(vec![1]));
+        assert_eq!(find_id(&list, 9), None);
+    }
+}
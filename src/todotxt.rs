@@ -0,0 +1,328 @@
+use crate::codec::{Codec, Section};
+use crate::todo::{self, Todo};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::io;
+
+// The todo.txt format (https://github.com/todotxt/todo.txt), one todo per line:
+// `x 2024-05-02 2024-05-01 (A) call mom +family @phone id:3`
+//
+// `+project` and `@context` become tags (`family`, `@phone`) but stay where they are in the
+// text, tags added in the app are written after it. Dates only have a day. Completed todos
+// lose their priority in todo.txt, so it is kept as `pri:A`. The file is flat, subtasks are
+// written after their parent with `parent:ID`, only parents get a new `id:`. Other `key:value`
+// pairs stay part of the text. Todos that did not change keep their line as it was.
+
+pub struct TodoTxt;
+
+impl Codec for TodoTxt {
+    fn decode(&self, doc: &str) -> io::Result<Vec<Section>> {
+        let lines: Vec<(Todo, Option<u64>)> = doc
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (mut todo, parent) = parse_line(line);
+                todo.source = Some(line.to_string());
+                (todo, parent)
+            })
+            .collect();
+        Ok(vec![Section {
+            todos: build(lines),
+            ..self.empty().remove(0)
+        }])
+    }
+
+    // the whole file is one todo list
+    fn encode(&self, _doc: &str, sections: &[Section]) -> String {
+        let mut out = String::new();
+        for section in sections {
+            write_lines(&section.todos, None, &mut out);
+        }
+        out
+    }
+
+    fn empty(&self) -> Vec<Section> {
+        vec![Section {
+            name: String::from("todo.txt"),
+            ..Default::default()
+        }]
+    }
+}
+
+// The tree of todos read in file order. A parent can come after its subtasks, a `parent:` that
+// points nowhere or in a circle stays in the text.
+fn build(lines: Vec<(Todo, Option<u64>)>) -> Vec<Todo> {
+    let index_of = |id: u64| lines.iter().position(|(todo, _)| todo.id == id && id != 0);
+    let parents: Vec<Option<usize>> = lines
+        .iter()
+        .map(|(_, parent)| parent.and_then(index_of))
+        .collect();
+    let circle = |start: usize| {
+        let mut at = parents[start];
+        for _ in 0..lines.len() {
+            match at {
+                Some(index) if index == start => return true,
+                Some(index) => at = parents[index],
+                None => return false,
+            }
+        }
+        // leads into a circle it is not part of
+        false
+    };
+    let parents: Vec<Option<usize>> = (0..lines.len())
+        .map(|index| parents[index].filter(|_| !circle(index)))
+        .collect();
+
+    let mut todos: Vec<Option<Todo>> = Vec::new();
+    for (index, (mut todo, parent)) in lines.into_iter().enumerate() {
+        if let (Some(id), None) = (parent, parents[index]) {
+            keep_parent(&mut todo, id);
+        }
+        todos.push(Some(todo));
+    }
+    // the deepest subtasks first, so each one is complete before it is added to its parent
+    let depth = |mut index: usize| {
+        let mut depth = 0;
+        while let Some(parent) = parents[index] {
+            depth += 1;
+            index = parent;
+        }
+        depth
+    };
+    let mut order: Vec<usize> = (0..todos.len()).rev().collect();
+    order.sort_by_key(|index| std::cmp::Reverse(depth(*index)));
+    for index in order {
+        if let Some(parent) = parents[index] {
+            let todo = todos[index].take().unwrap();
+            todos[parent].as_mut().unwrap().children.insert(0, todo);
+        }
+    }
+    todos.into_iter().flatten().collect()
+}
+
+fn keep_parent(todo: &mut Todo, parent: u64) {
+    todo.text = format!("{} parent:{}", todo.text, parent)
+        .trim_start()
+        .to_string();
+}
+
+// A todo and the id of its parent
+fn parse_line(line: &str) -> (Todo, Option<u64>) {
+    let mut todo = Todo::default();
+    let mut parent = None;
+    let mut words = line.split_whitespace().peekable();
+
+    if words.peek() == Some(&"x") {
+        words.next();
+        todo.done = true;
+        todo.completed = words
+            .next_if(|w| parse_date(w).is_some())
+            .and_then(parse_date);
+        if todo.completed.is_some() {
+            todo.created = words
+                .next_if(|w| parse_date(w).is_some())
+                .and_then(parse_date);
+        }
+    }
+    todo.priority = words
+        .next_if(|w| parse_priority(w).is_some())
+        .and_then(parse_priority);
+    if todo.created.is_none() {
+        todo.created = words
+            .next_if(|w| parse_date(w).is_some())
+            .and_then(parse_date);
+    }
+
+    let mut text = Vec::new();
+    for word in words {
+        let (key, value) = word.split_once(':').unwrap_or_default();
+        match (key, value.parse::<u64>()) {
+            ("id", Ok(id)) => {
+                todo.id = id;
+                todo.id_in_file = true;
+            }
+            ("parent", Ok(id)) => parent = Some(id),
+            ("pri", _) if todo.priority.is_none() && todo::parse_priority(value).is_some() => {
+                todo.priority = todo::parse_priority(value)
+            }
+            _ => {
+                if word.len() > 1 && word.starts_with('+') {
+                    todo.tags.push(word[1..].to_string());
+                } else if word.len() > 1 && word.starts_with('@') {
+                    todo.tags.push(word.to_string());
+                }
+                text.push(word);
+            }
+        }
+    }
+    todo.text = text.join(" ");
+    (todo, parent)
+}
+
+fn write_lines(todos: &[Todo], parent: Option<u64>, out: &mut String) {
+    for todo in todos {
+        let subtasks = !todo.children.is_empty();
+        let mut line = format_line(todo, parent, subtasks);
+        // a line that would be written the same is kept as it was, spaces and all
+        if let Some(source) = &todo.source {
+            let (mut before, mut before_parent) = parse_line(source);
+            if let (Some(id), None) = (before_parent, parent) {
+                keep_parent(&mut before, id);
+                before_parent = None;
+            }
+            if format_line(&before, before_parent, subtasks) == line {
+                line = source.clone();
+            }
+        }
+        *out += &(line + "\n");
+        write_lines(&todo.children, Some(todo.id).filter(|id| *id != 0), out);
+    }
+}
+
+fn format_line(todo: &Todo, parent: Option<u64>, subtasks: bool) -> String {
+    let mut words = Vec::new();
+    if todo.done {
+        words.push(String::from("x"));
+    }
+    match todo.completed.filter(|_| todo.done) {
+        Some(completed) => {
+            words.push(format_date(completed));
+            words.extend(todo.created.map(format_date));
+        }
+        // `x` in front of an open todo, as people mark them done by hand
+        None => {
+            words.extend(todo.priority.map(|p| format!("({})", p)));
+            words.extend(todo.created.map(format_date));
+        }
+    }
+    if !todo.text.is_empty() {
+        words.push(todo.text.clone());
+    }
+    for tag in &todo.tags {
+        if !in_text(&todo.text, tag) {
+            words.push(word(tag));
+        }
+    }
+    if todo.done && todo.completed.is_some() {
+        words.extend(todo.priority.map(|p| format!("pri:{}", p)));
+    }
+    // subtasks point at it
+    if todo.id != 0 && (todo.id_in_file || subtasks) {
+        words.push(format!("id:{}", todo.id));
+    }
+    words.extend(parent.map(|id| format!("parent:{}", id)));
+    words.join(" ")
+}
+
+// A tag as it is written in todo.txt, `+project` or `@context`
+pub fn word(tag: &str) -> String {
+    if tag.starts_with('@') {
+        tag.to_string()
+    } else {
+        format!("+{}", tag)
+    }
+}
+
+// Whether `text` holds `tag` already, as todo.txt keeps them
+pub fn in_text(text: &str, tag: &str) -> bool {
+    let tag = word(tag);
+    text.split_whitespace().any(|w| w == tag)
+}
+
+fn parse_priority(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(p), Some(')'), None) if p.is_ascii_uppercase() => Some(p),
+        _ => None,
+    }
+}
+
+fn parse_date(word: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+fn format_date(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FILE: &str = "\
+x 2024-05-02 2024-05-01 pay +home rent pri:B
+(A) 2024-05-01 call mom +family due:2024-05-03 @phone
+write report id:3
+check numbers parent:3
+x (A) 2024-01-01 done thing
+call   the  bank +money
+";
+
+    #[test]
+    fn fields() {
+        let section = TodoTxt.decode(FILE).unwrap().remove(0);
+        let rent = &section.todos[0];
+        assert!(rent.done);
+        assert_eq!(rent.priority, Some('B'));
+        assert_eq!(rent.completed, parse_date("2024-05-02"));
+        assert_eq!(rent.created, parse_date("2024-05-01"));
+
+        let call = &section.todos[1];
+        assert_eq!(call.text, "call mom +family due:2024-05-03 @phone");
+        assert_eq!(call.tags, ["family", "@phone"]);
+        assert_eq!((call.id, call.priority), (0, Some('A')));
+
+        assert_eq!(section.todos[2].children[0].text, "check numbers");
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut section = TodoTxt.decode(FILE).unwrap().remove(0);
+        assert_eq!(TodoTxt.encode("", &[section.clone()]), FILE);
+
+        // changed lines are written again, without losing the creation date
+        section.todos[3].text.push_str(" twice");
+        section.todos[4].tags.push(String::from("@phone"));
+        let lines: Vec<String> = TodoTxt
+            .encode("", &[section])
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(lines[4], "x (A) 2024-01-01 done thing twice");
+        assert_eq!(lines[5], "call the bank +money @phone");
+    }
+
+    #[test]
+    fn loose_lines() {
+        let section = TodoTxt
+            .decode("\nx done\n(a) lower case 2024-13-01\n")
+            .unwrap()
+            .remove(0);
+        assert!(section.todos[0].done);
+        assert_eq!(section.todos[0].text, "done");
+        assert_eq!(section.todos[1].priority, None);
+        assert_eq!(section.todos[1].text, "(a) lower case 2024-13-01");
+    }
+
+    #[test]
+    fn tags_from_the_app_go_after_the_text() {
+        let mut section = TodoTxt.decode("call +mom\n").unwrap().remove(0);
+        section.todos[0].tags.push(String::from("@phone"));
+        assert_eq!(TodoTxt.encode("", &[section]), "call +mom @phone\n");
+    }
+
+    #[test]
+    fn parents_can_come_later() {
+        let doc = "b.1 parent:2\nb id:2 parent:1\na id:1\nlost parent:9\np id:5 parent:6\nq id:6 parent:5\nr parent:6\n";
+        let section = TodoTxt.decode(doc).unwrap().remove(0);
+        let texts: Vec<&str> = section.todos.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["a", "lost parent:9", "p parent:6", "q parent:5"]);
+        assert_eq!(section.todos[0].children[0].text, "b");
+        assert_eq!(section.todos[0].children[0].children[0].text, "b.1");
+        assert_eq!(section.todos[3].children[0].text, "r");
+        assert_eq!(
+            TodoTxt.encode("", &[section]),
+            "a id:1\nb id:2 parent:1\nb.1 parent:2\nlost parent:9\np id:5 parent:6\nq id:6 parent:5\nr parent:6\n"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::app::App;
-use crate::state::Screen;
+use crate::state::{Mode, Screen};
 use ratatui::{prelude::*, widgets::*};
 
 pub fn ui(frame: &mut Frame, app: &mut App) {
@@ -13,12 +13,17 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     )
     .split(frame.size());
 
+    // a tab for every todo list of the file
     frame.render_widget(
-        Block::new()
-            .borders(Borders::TOP)
-            .cyan()
-            .title("TODO LIST")
-            .bold(),
+        Tabs::new(app.list_names())
+            .select(app.tab)
+            .highlight_style(app.theme.tab)
+            .block(
+                Block::new()
+                    .borders(Borders::TOP)
+                    .title("TODO LIST")
+                    .style(app.theme.border),
+            ),
         main_layout[0],
     );
 
@@ -32,97 +37,274 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     )
     .split(main_layout[1]);
 
-    app.update_len(inner_layout[0].x, inner_layout[1].x, inner_layout[2].y);
+    app.update_len(
+        inner_layout[0].height,
+        inner_layout[1].height,
+        inner_layout[2].height,
+    );
+    app.update_width(inner_layout[0].width, inner_layout[1].width);
 
-    if app.state.active == Screen::COMPLETED {
-        frame.render_widget(
-            Paragraph::new(app.show_completed_todos()).block(
-                Block::new()
-                    .borders(Borders::ALL)
-                    .white()
-                    .title("COMPLETED")
-                    .bold(),
-            ),
-            inner_layout[0],
-        );
-    } else {
-        frame.render_widget(
-            Paragraph::new(app.show_completed_todos()).block(
-                Block::new()
-                    .borders(Borders::ALL)
-                    .cyan()
-                    .title("COMPLETED")
-                    .bold(),
-            ),
-            inner_layout[0],
-        );
-    }
+    // the panel taking keys gets the focused border
+    let border = |screen| {
+        if app.state.active == screen {
+            app.theme.focused_border
+        } else {
+            app.theme.border
+        }
+    };
+    let (completed_border, active_border) = (border(Screen::COMPLETED), border(Screen::ACTIVE));
+    let completed = app.show_completed_todos();
+    app.completed_offset = render_todos(
+        frame,
+        inner_layout[0],
+        "COMPLETED",
+        completed_border,
+        completed,
+        app.shown_selected(Screen::COMPLETED),
+        app.completed_offset,
+    );
+    let active = app.show_active_todos();
+    app.active_offset = render_todos(
+        frame,
+        inner_layout[1],
+        "TODOS",
+        active_border,
+        active,
+        app.shown_selected(Screen::ACTIVE),
+        app.active_offset,
+    );
 
-    // render white widget if it is active else cyan
-    if app.state.active == Screen::ACTIVE {
-        frame.render_widget(
-            Paragraph::new(app.show_active_todos()).block(
-                Block::new()
-                    .borders(Borders::ALL)
-                    .white()
-                    .title("TODOS")
-                    .bold(),
-            ),
-            inner_layout[1],
-        );
-    } else {
-        frame.render_widget(
-            Paragraph::new(app.show_active_todos()).block(
-                Block::new()
-                    .borders(Borders::ALL)
-                    .cyan()
-                    .title("TODOS")
-                    .bold(),
-            ),
-            inner_layout[1],
-        );
+    // place the terminal cursor inside the todo being typed
+    if let Some((line, column)) = app.cursor() {
+        let area = match app.state.active {
+            Screen::COMPLETED => inner_layout[0],
+            Screen::ACTIVE => inner_layout[1],
+        };
+        let x = area.x + 1 + column as u16;
+        let y = area.y + 1 + line as u16;
+        if x < area.right() - 1 && y < area.bottom() - 1 {
+            frame.set_cursor(x, y);
+        }
     }
 
-    // display different tips for different screens
-    if app.state.active == Screen::ACTIVE {
-        frame.render_widget(
-            Paragraph::new(app.info_texts[0].to_string()).block(
-                Block::new()
-                    .borders(Borders::ALL)
-                    .cyan()
-                    .title("KEY BINDING")
-                    .bold(),
-            ),
-            inner_layout[2],
-        );
-    } else {
-        frame.render_widget(
-            Paragraph::new(app.info_texts[1].to_string()).block(
-                Block::new()
-                    .borders(Borders::ALL)
-                    .cyan()
-                    .title("KEY BINDING")
-                    .bold(),
-            ),
-            inner_layout[2],
-        );
-    }
+    // the keys of the current screen
+    frame.render_widget(
+        Paragraph::new(app.keymap.help(&app.state.mode, &app.state.active)).block(
+            Block::new()
+                .borders(Borders::ALL)
+                .title("KEY BINDING")
+                .style(app.theme.border),
+        ),
+        inner_layout[2],
+    );
 
+    // questions take over the status bar until they are answered
+    let status = |text: String| Span::styled(text, app.theme.status);
+    let status = match (&app.confirm, &app.message) {
+        (Some(confirm), _) => Line::from(status(confirm.get())),
+        _ if app.state.mode == Mode::SEARCH => {
+            Line::from(status(format!("/{}", app.editor.text())))
+        }
+        _ if app.state.mode == Mode::COMMAND => {
+            Line::from(status(format!(":{}", app.editor.text())))
+        }
+        (None, Some(message)) => Line::from(status(message.clone())),
+        (None, None) => Line::from(vec![
+            status(format!("CURRENTLY ACTIVE : {} -> ", app.state.active.get())),
+            Span::styled(app.state.mode.get().to_string(), app.theme.mode),
+            status(
+                app.pending_key
+                    .map_or(String::new(), |key| format!(" ({}-)", key))
+                    + &if app.state.mode == Mode::VISUAL {
+                        format!(" ({} selected)", app.selection().len())
+                    } else {
+                        String::new()
+                    }
+                    + &app
+                        .query()
+                        .map_or(String::new(), |query| format!("  |  /{}", query))
+                    + if app.is_dirty() {
+                        "  |  UNSAVED"
+                    } else {
+                        "  |  SAVED"
+                    }
+                    + "  |  "
+                    + &app.history.get(),
+            ),
+        ]),
+    };
     frame.render_widget(
-        Paragraph::new(
-            "CURRENTLY ACTIVE : ".to_owned()
-                + app.state.active.get()
-                + " -> "
-                + app.state.mode.get(),
-        )
-        .block(
+        Paragraph::new(status).block(
             Block::new()
                 .borders(Borders::TOP)
-                .cyan()
                 .title("MISC")
-                .bold(),
-        )
-        .bold(),
+                .style(app.theme.border),
+        ),
         main_layout[2],
     );
+    if let Some(column) = app.command_cursor() {
+        let area = main_layout[2];
+        frame.set_cursor(area.x + column as u16, area.y + 1);
+    }
+}
+
+// Draw a list of todos scrolled so the selected row is visible, with a scrollbar once the
+// list is longer than the panel. Returns the scroll offset to start from next frame.
+fn render_todos(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    border: Style,
+    items: Vec<ListItem<'static>>,
+    selected: usize,
+    offset: usize,
+) -> usize {
+    // scroll by lines, todos can wrap over several
+    let heights: Vec<usize> = items.iter().map(|item| item.height()).collect();
+    let block = Block::new()
+        .borders(Borders::ALL)
+        .title(title.to_string())
+        .style(border);
+
+    let mut state = ListState::default()
+        .with_offset(offset)
+        .with_selected(Some(selected));
+    frame.render_stateful_widget(List::new(items).block(block), area, &mut state);
+
+    let height = area.height.saturating_sub(2) as usize;
+    let lines: usize = heights.iter().sum();
+    if lines > height {
+        let above: usize = heights[..state.offset().min(heights.len())].iter().sum();
+        let mut scrollbar = ScrollbarState::new(lines - height).position(above);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            area.inner(&Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut scrollbar,
+        );
+    }
+    state.offset()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action;
+    use crate::storage::Memory;
+    use crate::theme::{Base, Theme};
+    use ratatui::backend::TestBackend;
+
+    // The screen as text, one string per row
+    fn screen(buffer: &Buffer) -> Vec<String> {
+        let area = buffer.area;
+        (area.top()..area.bottom())
+            .map(|y| {
+                (area.left()..area.right())
+                    .map(|x| buffer.get(x, y).symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut app = App::new(Box::new(Memory::default()));
+        for key in action::keys("iwrite docs<Enter>ship it<Enter>plan<Esc>ggm") {
+            app.handle_key(key);
+        }
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+        assert_eq!(
+            screen(terminal.backend().buffer()),
+            [
+                "TODO LIST───────────────────────────────────────────────────────────────────────",
+                " Todo                                                                           ",
+                "┌COMPLETED─────────────────────┐┌TODOS─────────────────────────┐┌KEY BINDING───┐",
+                "│  [x] write docs              ││  [ ] ship it                 ││Click TAB to s│",
+                "│                              ││  [ ] plan                    ││Click i to Ins│",
+                "│                              ││                              ││Click e to Edi│",
+                "│                              ││                              ││Click g to ent│",
+                "│                              ││                              ││Click gg / G f│",
+                "│                              ││                              ││Click PgUp / P│",
+                "│                              ││                              ││Click m to Com│",
+                "│                              ││                              ││Click d to Del│",
+                "│                              ││                              ││Click u / Ctrl│",
+                "│                              ││                              ││Click p for Pr│",
+                "└──────────────────────────────┘└──────────────────────────────┘└──────────────┘",
+                "MISC────────────────────────────────────────────────────────────────────────────",
+                "CURRENTLY ACTIVE : INCOMPLETE TODO'S SECTION -> READ MODE  |  UNSAVED  |  UNDO: ",
+            ]
+        );
+        // the selected todo is white, the others cyan
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer.get(34, 3).fg, Color::White);
+        assert_eq!(buffer.get(34, 4).fg, Color::Cyan);
+    }
+
+    #[test]
+    fn themes() {
+        let mut app = App::new(Box::new(Memory::default()));
+        for key in action::keys("ia<Enter>b<Esc>k") {
+            app.handle_key(key);
+        }
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+
+        // no colors anywhere, the selection is in reverse video
+        app.theme = Theme::new(Base::Mono);
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert!(buffer
+            .content
+            .iter()
+            .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+        assert!(buffer.get(34, 3).modifier.contains(Modifier::REVERSED));
+        assert!(!buffer.get(34, 4).modifier.contains(Modifier::REVERSED));
+
+        app.theme = Theme::new(Base::Light);
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer.get(34, 3).bg, Color::Blue);
+        assert_eq!(buffer.get(34, 4).fg, Color::Black);
+    }
+
+    #[test]
+    fn search_highlights_matches() {
+        let mut app = App::new(Box::new(Memory::default()));
+        for key in action::keys("iwrite docs<Enter>plan<Esc>/wd") {
+            app.handle_key(key);
+        }
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = screen(buffer);
+        assert!(rows[3].contains("[ ] write docs"));
+        assert!(!rows[4].contains("plan"));
+        assert!(rows[15].starts_with("/wd"));
+        // `w` and `d` of "write docs", which starts at column 39
+        assert_eq!(buffer.get(39, 3).fg, Color::Yellow);
+        assert_eq!(buffer.get(40, 3).fg, Color::White);
+        assert_eq!(buffer.get(45, 3).fg, Color::Yellow);
+    }
+
+    #[test]
+    fn visual_selection_stands_out() {
+        let mut app = App::new(Box::new(Memory::default()));
+        for key in action::keys("ia<Enter>b<Enter>c<Esc>ggvj") {
+            app.handle_key(key);
+        }
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+        terminal.draw(|frame| ui(frame, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer.get(34, 3).bg, Color::Cyan);
+        // the cursor is on a selected todo
+        assert_eq!(
+            (buffer.get(34, 4).fg, buffer.get(34, 4).bg),
+            (Color::White, Color::Cyan)
+        );
+        assert_eq!(buffer.get(34, 5).bg, Color::Reset);
+        assert!(screen(buffer)[15].contains("VISUAL MODE (2 selected)"));
+        assert!(screen(buffer)[3].contains("│Click v to lea│"));
+    }
 }
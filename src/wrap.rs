@@ -0,0 +1,96 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Word wrapping for todo text. Lines break after whitespace when possible and inside a word
+// only when the word alone is wider than the line. The whitespace a line breaks at stays at
+// the end of that line, so the lines cover the whole text and byte offsets (the cursor) can
+// be mapped to a line and column.
+
+// Byte ranges of the lines `text` wraps into at `width` columns, at least one line
+pub fn wrap(text: &str, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut start = 0;
+    // where the current line can be broken, after the last whitespace
+    let mut last_break = None;
+
+    for (index, g) in text.grapheme_indices(true) {
+        let space = g.trim().is_empty();
+        if !space && text[start..index + g.len()].width() > width && index > start {
+            let end = match last_break {
+                Some(end) if end > start => end,
+                _ => index,
+            };
+            lines.push(start..end);
+            start = end;
+            last_break = None;
+        }
+        if space {
+            last_break = Some(index + g.len());
+        }
+    }
+    lines.push(start..text.len());
+    lines
+}
+
+// Line and column of the byte offset `cursor` in the wrapped `text`
+pub fn locate(text: &str, width: usize, cursor: usize) -> (usize, usize) {
+    let lines = wrap(text, width);
+    let line = lines
+        .iter()
+        .position(|range| cursor < range.end)
+        .unwrap_or(lines.len() - 1);
+    let column = text[lines[line].start..cursor].width();
+    // a cursor after the whitespace a line breaks at shows at the start of the next line
+    if column >= width.max(1) && line + 1 < lines.len() {
+        return (line + 1, 0);
+    }
+    (line, column)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &str, width: usize) -> Vec<&str> {
+        wrap(text, width)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn breaks_at_whitespace() {
+        assert_eq!(lines("buy milk and eggs", 9), vec!["buy milk ", "and eggs"]);
+        // whitespace may hang over the edge
+        assert_eq!(lines("buy milk and eggs", 8), vec!["buy milk ", "and eggs"]);
+        assert_eq!(
+            lines("buy milk and eggs", 7),
+            vec!["buy ", "milk ", "and ", "eggs"]
+        );
+        assert_eq!(lines("short", 10), vec!["short"]);
+        assert_eq!(lines("", 10), vec![""]);
+    }
+
+    #[test]
+    fn long_words_are_split() {
+        assert_eq!(
+            lines("a supercalifragilistic b", 8),
+            vec!["a ", "supercal", "ifragili", "stic b"]
+        );
+        // wide characters take two columns
+        assert_eq!(lines("日本語です", 4), vec!["日本", "語で", "す"]);
+    }
+
+    #[test]
+    fn cursor_positions() {
+        let text = "buy milk and eggs";
+        assert_eq!(locate(text, 9, 0), (0, 0));
+        assert_eq!(locate(text, 9, 4), (0, 4));
+        assert_eq!(locate(text, 9, 9), (1, 0));
+        assert_eq!(locate(text, 9, text.len()), (1, 8));
+        // the cursor after a full last line stays on it
+        assert_eq!(locate("abcd", 4, 4), (0, 4));
+    }
+}